@@ -0,0 +1,55 @@
+//
+// checks run over a parsed file before it is handed to the interpreter
+//
+
+pub mod resolve;
+pub mod typecheck;
+
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::{inherit, lineage};
+use crate::parser::parser::{ExpressionKind, ExpressionType, HeadData, Node, PatternData, Values};
+use std::collections::HashMap;
+
+/// Runs every check over the file, returning all problems found ordered by where they are
+/// in the source. Any error means the interpreter would fail or silently fall back to a
+/// default somewhere while running it.
+pub fn check(head: &HeadData) -> Vec<Diagnostic> {
+    let mut diagnostics = resolve::check(head);
+    diagnostics.extend(typecheck::check(head));
+    diagnostics.sort_by_key(|d| d.span.map(|s| s.start));
+    diagnostics
+}
+
+// every pattern as the interpreter will run it, with what it extends merged in
+pub(crate) fn inherited_patterns(head: &HeadData) -> HashMap<&str, PatternData> {
+    let lookup = |name: &str| match head.definitions.get(name) {
+        Some(Node::Pattern(pd)) => Some(pd),
+        _ => None,
+    };
+    head.definitions
+        .iter()
+        .filter(|(_, node)| matches!(node, Node::Pattern(_)))
+        .map(|(name, _)| (name.as_str(), inherit(&lineage(name, lookup))))
+        .collect()
+}
+
+// names of the parameters of a path or function, `path p(t, speed) = ...` gives [t, speed]
+pub(crate) fn parameters(arguments: &ExpressionType) -> Vec<&str> {
+    match &arguments.kind {
+        ExpressionKind::Vector(parameters) => parameters
+            .iter()
+            .filter_map(|p| match &p.kind {
+                ExpressionKind::Variable(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// definitions in the order they were written, so diagnostics come out in a stable order
+pub(crate) fn in_source_order(definitions: &Values) -> Vec<(&String, &ExpressionType)> {
+    let mut definitions: Vec<(&String, &ExpressionType)> = definitions.iter().collect();
+    definitions.sort_by_key(|(_, expr)| expr.span.start);
+    definitions
+}
@@ -0,0 +1,827 @@
+use super::typecheck::{PATH_INPUTS, PATTERN_GLOBALS};
+use super::{in_source_order, inherited_patterns, parameters};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::interpreter::coroutine::MAX_RUN_DEPTH;
+use crate::interpreter::entity::DEFERRED_FIELDS;
+use crate::interpreter::ps_funcs::{self, BUILTINS};
+use crate::interpreter::{extends_itself, lineage, Inherits};
+use crate::parser::parser::*;
+use crate::parser::types::Span;
+use std::collections::{HashMap, HashSet};
+
+/// Checks every name in the file refers to something: variables, `type =` bullets,
+/// `position_fn`/`velocity_fn` paths, patterns that are run, fired or used as a `behavior`
+/// and called functions, suggesting the closest name in scope for typos. Also warns about
+/// bullets, paths, functions and variables that are never used. Patterns that extend or
+/// run themselves, and bullet or spawn fields that need themselves, are reported too.
+pub fn check(head: &HeadData) -> Vec<Diagnostic> {
+    let mut resolver = Resolver {
+        patterns: HashMap::new(),
+        inherited: inherited_patterns(head),
+        bullets: HashMap::new(),
+        paths: HashMap::new(),
+        functions: HashMap::new(),
+        used_bullets: HashSet::new(),
+        used_paths: HashSet::new(),
+        used_functions: HashSet::new(),
+        scopes: Vec::new(),
+        unbound: Severity::Error,
+        diagnostics: Vec::new(),
+    };
+    let mut definitions: Vec<(&String, &Node)> = head.definitions.iter().collect();
+    definitions.sort_by_key(|(_, node)| node.span().start);
+    for (name, node) in &definitions {
+        match node {
+            Node::Pattern(pd) => {
+                resolver.patterns.insert(name.as_str(), pd);
+            }
+            Node::Bullet(bd) => {
+                resolver.bullets.insert(name.as_str(), bd);
+            }
+            Node::Path(pd) => {
+                resolver.paths.insert(name.as_str(), pd);
+            }
+            Node::Function(fd) => {
+                resolver.functions.insert(name.as_str(), fd);
+            }
+            _ => {}
+        }
+    }
+    for (name, node) in &definitions {
+        match node {
+            Node::Pattern(pd) => resolver.resolve_pattern(name, pd),
+            Node::Bullet(bd) => resolver.resolve_bullet(name, bd),
+            Node::Path(pd) => resolver.resolve_path(pd),
+            Node::Function(fd) => resolver.resolve_function_definition(name, fd),
+            _ => {}
+        }
+    }
+    for (name, node) in &definitions {
+        let (kind, used) = match node {
+            Node::Bullet(_) => ("bullet", &resolver.used_bullets),
+            Node::Path(_) => ("path", &resolver.used_paths),
+            Node::Function(_) => ("function", &resolver.used_functions),
+            _ => continue,
+        };
+        if !used.contains(name.as_str()) {
+            resolver.diagnostics.push(
+                Diagnostic::warning(
+                    format!("{} `{}` is never used", kind, name),
+                    Some(node.span()),
+                )
+                .with_label("defined here"),
+            );
+        }
+    }
+    resolver.diagnostics
+}
+
+struct Binding {
+    name: String,
+    span: Span,
+    used: bool,
+    // locals assigned in a block are warned about, parameters and fields aren't
+    warn_unused: bool,
+}
+
+struct Resolver<'a> {
+    patterns: HashMap<&'a str, &'a PatternData>,
+    // patterns with what they extend merged in
+    inherited: HashMap<&'a str, PatternData>,
+    bullets: HashMap<&'a str, &'a BulletData>,
+    paths: HashMap<&'a str, &'a PathData>,
+    functions: HashMap<&'a str, &'a FunctionData>,
+    used_bullets: HashSet<&'a str>,
+    used_paths: HashSet<&'a str>,
+    used_functions: HashSet<&'a str>,
+    // innermost last, later bindings in a scope shadow earlier ones
+    scopes: Vec<Vec<Binding>>,
+    // bullet and path bodies can also see the scope they're spawned from, which isn't known
+    // here, so names missing from them are only warned about
+    unbound: Severity,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Resolver<'a> {
+    fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        for binding in self.scopes.pop().unwrap_or_default() {
+            if binding.warn_unused && !binding.used {
+                self.diagnostics.push(
+                    Diagnostic::warning(
+                        format!("unused variable `{}`", binding.name),
+                        Some(binding.span),
+                    )
+                    .with_label("assigned here but never used"),
+                );
+            }
+        }
+    }
+
+    fn bind(&mut self, name: &str, span: Span, warn_unused: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Binding {
+                name: name.to_string(),
+                span,
+                used: false,
+                warn_unused,
+            });
+        }
+    }
+
+    fn bind_all<'n>(&mut self, names: impl IntoIterator<Item = &'n str>) {
+        for name in names {
+            self.bind(name, Span::default(), false);
+        }
+    }
+
+    fn is_bound(&self, name: &str) -> bool {
+        self.scopes.iter().flatten().any(|b| b.name == name)
+    }
+
+    // marks the binding as used
+    fn lookup(&mut self, name: &str) -> bool {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.iter_mut().rev().find(|b| b.name == name) {
+                binding.used = true;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn report_missing(
+        &mut self,
+        severity: Severity,
+        what: &str,
+        name: &str,
+        span: Span,
+        candidates: Vec<&str>,
+    ) {
+        let message = format!("cannot find {} `{}`", what, name);
+        let diagnostic = match severity {
+            Severity::Error => Diagnostic::error(message, Some(span)),
+            Severity::Warning => Diagnostic::warning(message, Some(span)),
+        }
+        .with_label(format!("no {} named `{}`", what, name));
+        self.diagnostics.push(match did_you_mean(name, candidates) {
+            Some(suggestion) => diagnostic.with_help(format!("did you mean `{}`?", suggestion)),
+            None => diagnostic,
+        });
+    }
+
+    // a pattern sees what it extends, but only its own definitions are resolved here
+    fn resolve_pattern(&mut self, name: &str, pd: &PatternData) {
+        let patterns = self.patterns.clone();
+        self.resolve_parent("pattern", name, pd.parent(), pd.span, &patterns);
+        if self.runs_itself(name) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("pattern `{}` runs itself", name), Some(pd.span))
+                    .with_label("waits for itself to finish, so never does")
+                    .with_help("use `fire` to start it on its own emitter instead"),
+            );
+        } else {
+            let depth = self.run_depth(name, &mut Vec::new(), &mut HashMap::new());
+            if depth > MAX_RUN_DEPTH {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!("pattern `{}` runs patterns {} deep", name, depth),
+                        Some(pd.span),
+                    )
+                    .with_label(format!(
+                        "only {} can run inside each other, the runs past that are skipped",
+                        MAX_RUN_DEPTH
+                    ))
+                    .with_help("use `fire` to start some of them on their own emitters instead"),
+                );
+            }
+        }
+        self.unbound = Severity::Error;
+        self.push_scope();
+        self.bind_all(PATTERN_GLOBALS.iter().map(|(name, _)| *name));
+        let inherited = self
+            .inherited
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| pd.clone());
+        for parameter in parameters(&inherited.arguments) {
+            self.bind(parameter, inherited.arguments.span, false);
+        }
+        self.bind_all(inherited.definitions.keys().map(|name| name.as_str()));
+        for (name, expr) in in_source_order(&pd.definitions) {
+            match (name.as_str(), &expr.kind) {
+                // `time`, `cycles` or `loop`, checked with the other types
+                ("iteration_type", _) => {}
+                ("actions", ExpressionKind::Block(block)) => self.resolve_block(block),
+                _ => self.resolve_expr(expr),
+            }
+        }
+        self.pop_scope();
+    }
+
+    // `pattern a extends b` and `bullet a : b` need b to exist and not lead back to a
+    fn resolve_parent<T: Inherits>(
+        &mut self,
+        kind: &str,
+        name: &str,
+        parent: Option<&str>,
+        span: Span,
+        definitions: &HashMap<&'a str, &'a T>,
+    ) {
+        let parent = match parent {
+            Some(parent) => parent,
+            None => return,
+        };
+        if !definitions.contains_key(parent) {
+            let candidates: Vec<&str> = definitions.keys().copied().collect();
+            self.report_missing(Severity::Error, kind, parent, span, candidates);
+            return;
+        }
+        if extends_itself(name, |n| definitions.get(n).copied()) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("{} `{}` extends itself", kind, name), Some(span))
+                    .with_label(format!("`{}` leads back here", parent)),
+            );
+        }
+    }
+
+    // whether running name leads to running it again, with every run waiting on the next
+    fn runs_itself(&self, name: &str) -> bool {
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut pending = self.runs(name);
+        while let Some(next) = pending.pop() {
+            if next == name {
+                return true;
+            }
+            if visited.insert(next) {
+                pending.extend(self.runs(next));
+            }
+        }
+        false
+    }
+
+    // the most runs nested inside each other once name is started, not following cycles
+    fn run_depth(
+        &self,
+        name: &str,
+        visiting: &mut Vec<String>,
+        depths: &mut HashMap<String, usize>,
+    ) -> usize {
+        if let Some(depth) = depths.get(name) {
+            return *depth;
+        }
+        visiting.push(name.to_string());
+        let mut depth = 0;
+        for next in self.runs(name) {
+            if !visiting.iter().any(|v| v == next) {
+                depth = depth.max(1 + self.run_depth(next, visiting, depths));
+            }
+        }
+        visiting.pop();
+        depths.insert(name.to_string(), depth);
+        depth
+    }
+
+    fn runs(&self, name: &str) -> Vec<&str> {
+        let mut found = Vec::new();
+        if let Some(ExpressionType {
+            kind: ExpressionKind::Block(block),
+            ..
+        }) = self
+            .inherited
+            .get(name)
+            .and_then(|pd| pd.definitions.get("actions"))
+        {
+            run_statements(block, &mut found);
+        }
+        found
+    }
+
+    // a bullet sees the fields of the bullets it extends
+    fn resolve_bullet(&mut self, name: &str, bd: &BulletData) {
+        let bullets = self.bullets.clone();
+        self.resolve_parent("bullet", name, bd.parent(), bd.span, &bullets);
+        if let Some((&parent, _)) = bd.parent().and_then(|p| bullets.get_key_value(p)) {
+            self.used_bullets.insert(parent);
+        }
+        self.unbound = Severity::Warning;
+        self.push_scope();
+        self.bind_all(PATTERN_GLOBALS.iter().map(|(name, _)| *name));
+        let inherited = bd
+            .parent()
+            .map(|parent| self.prefab(parent))
+            .unwrap_or_default();
+        self.resolve_entity_fields(&bd.definitions, inherited);
+        self.pop_scope();
+    }
+
+    fn resolve_path(&mut self, pd: &PathData) {
+        self.unbound = Severity::Warning;
+        self.push_scope();
+        self.bind_all(PATTERN_GLOBALS.iter().map(|(name, _)| *name));
+        for parameter in parameters(&pd.arguments) {
+            self.bind(parameter, pd.arguments.span, false);
+        }
+        self.bind_all(pd.definitions.keys().map(|name| name.as_str()));
+        self.bind_all(PATH_INPUTS.iter().map(|(name, _)| *name));
+        for (_, expr) in in_source_order(&pd.definitions) {
+            self.resolve_expr(expr);
+        }
+        self.pop_scope();
+    }
+
+    // functions only see their parameters, everything else comes in as an argument
+    fn resolve_function_definition(&mut self, name: &str, fd: &FunctionData) {
+        if BUILTINS.contains(&name) {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!("function `{}` has the same name as a builtin", name),
+                    Some(fd.span),
+                )
+                .with_label(format!("calls to `{}` use the builtin", name)),
+            );
+        }
+        self.unbound = Severity::Error;
+        self.push_scope();
+        if let ExpressionKind::Vector(parameters) = &fd.arguments.kind {
+            for parameter in parameters {
+                if let ExpressionKind::Variable(name) = &parameter.kind {
+                    self.bind(name, parameter.span, false);
+                }
+            }
+        }
+        self.resolve_expr(&fd.body);
+        self.pop_scope();
+    }
+
+    fn resolve_block(&mut self, block: &Block) {
+        self.push_scope();
+        for statement in &block.statements {
+            match statement {
+                Node::Assign(ad) => {
+                    // resolved first, so `x = x + 1` reads the previous x
+                    self.resolve_expr(&ad.rvalue);
+                    self.bind(&ad.lvalue, ad.span, true);
+                }
+                Node::Spawn(sd) => self.resolve_entity_fields(&sd.definitions, Values::new()),
+                Node::Wait(wd) => self.resolve_wait(wd),
+                Node::Return(rd) => self.resolve_expr(&rd.value),
+                Node::Run(cd) | Node::Fire(cd) => self.resolve_pattern_call(cd),
+                Node::For(fd) => {
+                    self.push_scope();
+                    self.bind_all(fd.initial_definitions.keys().map(|name| name.as_str()));
+                    if let Condition::When(e) | Condition::Unless(e) = &fd.condition {
+                        self.resolve_expr(e);
+                    }
+                    self.resolve_block(&fd.body);
+                    self.pop_scope();
+                }
+                Node::Head(_)
+                | Node::Pattern(_)
+                | Node::Bullet(_)
+                | Node::Path(_)
+                | Node::Function(_) => {}
+            }
+        }
+        self.pop_scope();
+    }
+
+    // bullet and spawn blocks, along with the fields of the bullets they extend or spawn
+    //   a field sees the other fields and the scope around the block, but not itself: its
+    //   own name means whatever it shadows, as Entity::from_values evaluates it
+    fn resolve_entity_fields(&mut self, definitions: &Values, inherited: Values) {
+        let mut fields = inherited;
+        if let Some(expr) = definitions.get("type") {
+            fields.extend(self.resolve_bullet_type(expr));
+        }
+        fields.extend(definitions.iter().map(|(k, v)| (k.clone(), v.clone())));
+        for (name, expr) in in_source_order(definitions) {
+            if name == "type" {
+                continue;
+            }
+            let refers_to_itself = !self.is_bound(name) && ps_funcs::constant(name).is_none();
+            self.push_scope();
+            for field in fields.keys().filter(|field| *field != name) {
+                self.bind(field, Span::default(), false);
+            }
+            if refers_to_itself {
+                self.report_self_reference(name, expr);
+            }
+            match name.as_str() {
+                "position_fn" | "velocity_fn" => self.resolve_path_call(expr),
+                "behavior" => self.resolve_behavior(expr),
+                "speed_over_time" => {
+                    self.bind("t", Span::default(), false);
+                    self.resolve_expr(expr);
+                }
+                _ => self.resolve_expr(expr),
+            }
+            self.pop_scope();
+        }
+        self.report_field_cycles(definitions, &fields);
+    }
+
+    // a field reading its own name when there's nothing outside the block by that name
+    //   bound afterwards, so it isn't reported as missing as well
+    fn report_self_reference(&mut self, name: &str, expr: &ExpressionType) {
+        let mut found = Vec::new();
+        variables(expr, &mut found);
+        if let Some((_, span)) = found.into_iter().find(|(variable, _)| *variable == name) {
+            let message = format!("field `{}` refers to itself", name);
+            let diagnostic = match self.unbound {
+                Severity::Error => Diagnostic::error(message, Some(span)),
+                Severity::Warning => Diagnostic::warning(message, Some(span)),
+            };
+            self.diagnostics.push(
+                diagnostic
+                    .with_label(format!("no `{}` outside this block to read", name))
+                    .with_help("a field can read the other fields and the variables around the block, but not itself"),
+            );
+            self.bind(name, Span::default(), false);
+        }
+    }
+
+    // fields that need each other can't be evaluated, so the entity is left with defaults
+    //   reported once per cycle, at the first of the block's own fields in it
+    fn report_field_cycles(&mut self, definitions: &Values, fields: &Values) {
+        let evaluated: Values = fields
+            .iter()
+            .filter(|(name, _)| !DEFERRED_FIELDS.contains(&name.as_str()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let mut reported: HashSet<&str> = HashSet::new();
+        for (name, expr) in in_source_order(definitions) {
+            if reported.contains(name.as_str()) {
+                continue;
+            }
+            let cycle = match field_cycle(name, &evaluated) {
+                Some(cycle) => cycle,
+                None => continue,
+            };
+            let mut label = format!("`{}` needs `{}`", name, cycle[1]);
+            for field in cycle[2..].iter().chain([&name.as_str()]) {
+                label.push_str(&format!(", which needs `{}`", field));
+            }
+            let message = format!("field `{}` depends on itself", name);
+            let diagnostic = match self.unbound {
+                Severity::Error => Diagnostic::error(message, Some(expr.span)),
+                Severity::Warning => Diagnostic::warning(message, Some(expr.span)),
+            };
+            self.diagnostics.push(
+                diagnostic.with_label(label).with_help(
+                    "fields in a cycle can't be evaluated, so are left at their defaults",
+                ),
+            );
+            for field in cycle {
+                if let Some((key, _)) = definitions.get_key_value(field) {
+                    reported.insert(key.as_str());
+                }
+            }
+        }
+    }
+
+    // the fields of the bullet named, or nothing if there is no such bullet
+    fn resolve_bullet_type(&mut self, expr: &ExpressionType) -> Values {
+        let name = match &expr.kind {
+            ExpressionKind::Variable(name) => name,
+            _ => {
+                self.diagnostics.push(
+                    Diagnostic::error("`type` should name a bullet", Some(expr.span))
+                        .with_label("not a bullet name"),
+                );
+                return Values::new();
+            }
+        };
+        match self.bullets.get_key_value(name.as_str()) {
+            Some((&key, _)) => {
+                self.used_bullets.insert(key);
+                self.prefab(key)
+            }
+            None => {
+                let candidates: Vec<&str> = self.bullets.keys().copied().collect();
+                self.report_missing(Severity::Error, "bullet", name, expr.span, candidates);
+                Values::new()
+            }
+        }
+    }
+
+    // fields of a bullet and everything it extends, each bullet overriding its parent's
+    fn prefab(&self, name: &str) -> Values {
+        let mut definitions = Values::new();
+        for bd in lineage(name, |n| self.bullets.get(n).copied()).iter().rev() {
+            definitions.extend(bd.definitions.clone());
+        }
+        definitions
+    }
+
+    fn resolve_path_call(&mut self, expr: &ExpressionType) {
+        // anything that isn't a call is reported by the type checker
+        if let ExpressionKind::Expr(ArithmeticExpression::Call(name, arguments)) = &expr.kind {
+            if let Some((&key, _)) = self.paths.get_key_value(name.as_str()) {
+                self.used_paths.insert(key);
+            } else {
+                let candidates: Vec<&str> = self.paths.keys().copied().collect();
+                self.report_missing(Severity::Error, "path", name, expr.span, candidates);
+            }
+            // arguments are evaluated along with the path each frame, so can use t too
+            self.push_scope();
+            self.bind_all(PATH_INPUTS.iter().map(|(name, _)| *name));
+            for argument in arguments {
+                self.resolve_expr(argument);
+            }
+            self.pop_scope();
+        }
+    }
+
+    fn resolve_pattern_call(&mut self, cd: &PatternCallData) {
+        self.resolve_pattern_arguments(&cd.pattern, &cd.arguments, cd.span);
+    }
+
+    // anything that isn't a pattern name or call is reported by the type checker
+    fn resolve_behavior(&mut self, expr: &ExpressionType) {
+        match &expr.kind {
+            ExpressionKind::Variable(pattern) => {
+                self.resolve_pattern_arguments(pattern, &[], expr.span)
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Call(pattern, arguments)) => {
+                self.resolve_pattern_arguments(pattern, arguments, expr.span)
+            }
+            _ => self.resolve_expr(expr),
+        }
+    }
+
+    fn resolve_pattern_arguments(
+        &mut self,
+        pattern: &str,
+        arguments: &[ExpressionType],
+        span: Span,
+    ) {
+        if !self.patterns.contains_key(pattern) {
+            let candidates: Vec<&str> = self.patterns.keys().copied().collect();
+            self.report_missing(Severity::Error, "pattern", pattern, span, candidates);
+        }
+        for argument in arguments {
+            self.resolve_expr(argument);
+        }
+    }
+
+    fn resolve_wait(&mut self, wd: &WaitData) {
+        match wd {
+            WaitData::Frames(e) | WaitData::Time(e) => self.resolve_expr(e),
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &ExpressionType) {
+        match &expr.kind {
+            ExpressionKind::Variable(name) => {
+                if !self.lookup(name) && ps_funcs::constant(name).is_none() {
+                    let candidates: Vec<String> = self
+                        .scopes
+                        .iter()
+                        .flatten()
+                        .map(|b| b.name.clone())
+                        .collect();
+                    self.report_missing(
+                        self.unbound,
+                        "variable",
+                        name,
+                        expr.span,
+                        candidates.iter().map(|c| c.as_str()).collect(),
+                    );
+                }
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Unary(_, argument)) => {
+                self.resolve_expr(argument);
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Call(name, arguments)) => {
+                self.resolve_function(name, expr.span);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Binary(_, lhs, rhs)) => {
+                self.resolve_expr(lhs);
+                self.resolve_expr(rhs);
+            }
+            ExpressionKind::Vector(elements) => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            ExpressionKind::Duration(wd) => self.resolve_wait(wd),
+            ExpressionKind::Block(block) => self.resolve_block(block),
+            ExpressionKind::Int(_)
+            | ExpressionKind::Float(_)
+            | ExpressionKind::String(_)
+            | ExpressionKind::Range(..)
+            | ExpressionKind::None => {}
+        }
+    }
+
+    fn resolve_function(&mut self, name: &str, span: Span) {
+        if BUILTINS.contains(&name) {
+            return;
+        }
+        if let Some((&key, _)) = self.functions.get_key_value(name) {
+            self.used_functions.insert(key);
+            return;
+        }
+        if self.paths.contains_key(name) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("path `{}` called as a function", name), Some(span))
+                    .with_label("paths can't be called in expressions")
+                    .with_help("use it as `position_fn` or `velocity_fn` of a spawn"),
+            );
+            return;
+        }
+        let mut candidates = BUILTINS.to_vec();
+        candidates.extend(self.functions.keys().copied());
+        self.report_missing(Severity::Error, "function", name, span, candidates);
+    }
+}
+
+// patterns named by run statements in a block, at any depth
+fn run_statements<'b>(block: &'b Block, found: &mut Vec<&'b str>) {
+    for statement in &block.statements {
+        match statement {
+            Node::Run(cd) => found.push(&cd.pattern),
+            Node::For(fd) => run_statements(&fd.body, found),
+            _ => {}
+        }
+    }
+}
+
+// variables an expression reads, outside of any blocks in it
+fn variables<'e>(expr: &'e ExpressionType, found: &mut Vec<(&'e str, Span)>) {
+    match &expr.kind {
+        ExpressionKind::Variable(name) => found.push((name, expr.span)),
+        ExpressionKind::Expr(ArithmeticExpression::Unary(_, argument)) => {
+            variables(argument, found);
+        }
+        ExpressionKind::Expr(ArithmeticExpression::Call(_, arguments))
+        | ExpressionKind::Vector(arguments) => {
+            for argument in arguments {
+                variables(argument, found);
+            }
+        }
+        ExpressionKind::Expr(ArithmeticExpression::Binary(_, lhs, rhs)) => {
+            variables(lhs, found);
+            variables(rhs, found);
+        }
+        ExpressionKind::Duration(wd) => match wd.as_ref() {
+            WaitData::Frames(e) | WaitData::Time(e) => variables(e, found),
+        },
+        _ => {}
+    }
+}
+
+// the fields name needs to be evaluated, each needing the next, that lead back to it
+fn field_cycle<'v>(name: &'v str, fields: &'v Values) -> Option<Vec<&'v str>> {
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut pending = vec![vec![name]];
+    while let Some(chain) = pending.pop() {
+        let last = chain[chain.len() - 1];
+        let mut found = Vec::new();
+        if let Some(expr) = fields.get(last) {
+            variables(expr, &mut found);
+        }
+        for (dependency, _) in found {
+            if dependency == last || !fields.contains_key(dependency) {
+                continue;
+            }
+            if dependency == name {
+                return Some(chain);
+            }
+            if visited.insert(dependency) {
+                let mut next = chain.clone();
+                next.push(dependency);
+                pending.push(next);
+            }
+        }
+    }
+    None
+}
+
+// the candidate closest to name, if it is close enough to be a typo
+fn did_you_mean<'c>(name: &str, candidates: Vec<&'c str>) -> Option<&'c str> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|c| *c != name)
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+// levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::Parser;
+
+    fn diagnose(source: &str) -> Vec<(Severity, String, Option<String>, &str, usize)> {
+        let head = match Parser::parse_from_string(source.to_string()) {
+            Ok(Node::Head(head)) => head,
+            other => panic!("didn't parse: {:?}", other.err()),
+        };
+        check(&head)
+            .into_iter()
+            .map(|d| {
+                let span = d.span.expect("resolve errors point into the source");
+                (
+                    d.severity,
+                    d.message,
+                    d.help,
+                    &source[span.start..span.end],
+                    span.line,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn misspelled_names() {
+        let source = "bullet mid_sized = {
+ speed = 1;
+}
+pattern p = {
+ actions = {
+  spawn { type = mid_size; }
+  angle = towards_plyer;
+  spawn { rotation = angle + nowhere_near; }
+ }
+}";
+        let diagnostics = diagnose(source);
+        assert_eq!(
+            diagnostics[..3],
+            [
+                (
+                    Severity::Error,
+                    "cannot find bullet `mid_size`".into(),
+                    Some("did you mean `mid_sized`?".into()),
+                    "mid_size",
+                    6
+                ),
+                (
+                    Severity::Error,
+                    "cannot find variable `towards_plyer`".into(),
+                    Some("did you mean `towards_player`?".into()),
+                    "towards_plyer",
+                    7
+                ),
+                (
+                    Severity::Error,
+                    "cannot find variable `nowhere_near`".into(),
+                    None,
+                    "nowhere_near",
+                    8
+                ),
+            ]
+        );
+        // nothing names it correctly, so the bullet is unused as well
+        assert_eq!(diagnostics.len(), 4);
+    }
+
+    #[test]
+    fn unused_bullets() {
+        let source = "bullet unused = {
+ speed = 1;
+}
+bullet used = {
+ speed = 2;
+}
+pattern p = {
+ actions = {
+  spawn { type = used; }
+ }
+}";
+        assert_eq!(
+            diagnose(source),
+            [(
+                Severity::Warning,
+                "bullet `unused` is never used".into(),
+                None,
+                "bullet unused = {\n speed = 1;\n}",
+                1
+            )]
+        );
+    }
+}
@@ -0,0 +1,983 @@
+use super::{in_source_order, inherited_patterns, parameters};
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::entity::{BlendMode, BoundsPolicy, HitboxType};
+use crate::interpreter::ps_funcs;
+use crate::parser::parser::*;
+use crate::parser::types::{Op, Span};
+use std::collections::HashMap;
+use std::fmt;
+
+// the type of an expression as far as it can be known without running it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Int,
+    Float,
+    String,
+    IntVec,
+    FloatVec,
+    StrVec,
+    Bool,
+    Duration,
+    Range,
+    Block,
+    // names that aren't values (`iteration_type = time`), or variables bound at run time
+    //   anything involving an unknown type is let through
+    Unknown,
+}
+
+impl Type {
+    fn is_numeric(self) -> bool {
+        matches!(self, Type::Int | Type::Float)
+    }
+
+    fn is_numeric_vec(self) -> bool {
+        matches!(self, Type::IntVec | Type::FloatVec)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::String => "string",
+            Type::IntVec => "int vector",
+            Type::FloatVec => "float vector",
+            Type::StrVec => "string vector",
+            Type::Bool => "bool",
+            Type::Duration => "duration",
+            Type::Range => "range",
+            Type::Block => "block",
+            Type::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// what a field of a pattern, bullet or spawn has to be
+#[derive(Debug, Clone, Copy)]
+enum Expected {
+    Number,
+    Int,
+    NumericVec,
+    NumberOrVec,
+    String,
+    Duration,
+    Block,
+    // `name(args)` naming a path
+    PathCall,
+    // `name` or `name(args)` naming a pattern
+    PatternCall,
+    // a bare name, checked by name resolution rather than typed
+    Name,
+}
+
+impl Expected {
+    fn accepts(self, t: Type) -> bool {
+        match self {
+            _ if t == Type::Unknown => true,
+            Expected::Number => t.is_numeric(),
+            Expected::Int => t == Type::Int,
+            Expected::NumericVec => t.is_numeric_vec(),
+            Expected::NumberOrVec => t.is_numeric() || t.is_numeric_vec(),
+            Expected::String => t == Type::String,
+            Expected::Duration => t == Type::Duration,
+            Expected::Block => t == Type::Block,
+            Expected::PathCall | Expected::PatternCall | Expected::Name => true,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Expected::Number => "a number",
+            Expected::Int => "an int",
+            Expected::NumericVec => "a vector of numbers",
+            Expected::NumberOrVec => "a number or a vector of numbers",
+            Expected::String => "a string",
+            Expected::Duration => "a duration such as `2 seconds` or `30 frames`",
+            Expected::Block => "a block of statements",
+            Expected::PathCall => "a path call such as `name(t, ...)`",
+            Expected::PatternCall => "a pattern such as `name` or `name(...)`",
+            Expected::Name => "a name",
+        }
+    }
+}
+
+// fields Entity::from_values reads from bullet and spawn blocks
+fn entity_field(name: &str) -> Option<Expected> {
+    match name {
+        "lifetime" | "rotation" | "speed" | "bounds_margin" | "scale" | "alpha" => {
+            Some(Expected::Number)
+        }
+        "z_order" => Some(Expected::Int),
+        "angular_velocity" | "max_speed" | "min_speed" | "friction" | "speed_over_time" => {
+            Some(Expected::Number)
+        }
+        "acceleration" => Some(Expected::NumberOrVec),
+        "position" | "velocity" | "color" | "hitbox" => Some(Expected::NumericVec),
+        "sprite" | "shape" | "bounds" | "blend" => Some(Expected::String),
+        "position_fn" | "velocity_fn" => Some(Expected::PathCall),
+        "behavior" => Some(Expected::PatternCall),
+        "type" => Some(Expected::Name),
+        _ => None,
+    }
+}
+
+type Scope = HashMap<String, Type>;
+
+// globals of a running pattern, see Interpreter::create_globals
+pub(crate) const PATTERN_GLOBALS: [(&str, Type); 11] = [
+    ("entity_position", Type::FloatVec),
+    ("entity_rotation", Type::Float),
+    ("entity_velocity", Type::FloatVec),
+    ("towards_player", Type::Float),
+    ("player_position", Type::FloatVec),
+    ("player_velocity", Type::FloatVec),
+    ("distance_to_player", Type::Float),
+    ("elapsed", Type::Int),
+    ("elapsed_seconds", Type::Float),
+    ("bullets_near_player", Type::Int),
+    ("bullets_near", Type::Int),
+];
+
+// inputs of a path function each frame, see bytecode::PATH_INPUTS
+//   these shadow the path's own parameters
+pub(crate) const PATH_INPUTS: [(&str, Type); 5] = [
+    ("t", Type::Int),
+    ("towards_player", Type::Float),
+    ("player_position", Type::FloatVec),
+    ("player_velocity", Type::FloatVec),
+    ("distance_to_player", Type::Float),
+];
+
+fn scope_of(names: &[(&str, Type)]) -> Scope {
+    names
+        .iter()
+        .map(|(name, t)| (name.to_string(), *t))
+        .collect()
+}
+
+// what a bullet or spawn field sees: the other fields, with its own name meaning whatever
+// the field shadows, as Entity::from_values evaluates it
+fn field_scope(fields: &Scope, outer: &Scope, name: &str) -> Scope {
+    let mut scope = fields.clone();
+    match outer.get(name) {
+        Some(t) => scope.insert(name.to_string(), *t),
+        None => scope.remove(name),
+    };
+    scope
+}
+
+/// Infers the type of every expression in the file, reporting operators applied to the
+/// wrong types, non-boolean `for` conditions, fields of the wrong type and path calls
+/// and pattern runs with the wrong number of arguments.
+pub fn check(head: &HeadData) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        patterns: inherited_patterns(head),
+        paths: HashMap::new(),
+        functions: HashMap::new(),
+        diagnostics: Vec::new(),
+    };
+    for (name, node) in &head.definitions {
+        match node {
+            Node::Path(pd) => {
+                checker.paths.insert(name.as_str(), pd);
+            }
+            Node::Function(fd) => {
+                checker.functions.insert(name.as_str(), fd);
+            }
+            _ => {}
+        }
+    }
+    let mut definitions: Vec<(&String, &Node)> = head.definitions.iter().collect();
+    definitions.sort_by_key(|(_, node)| node.span().start);
+    for (name, node) in definitions {
+        match node {
+            Node::Pattern(pd) => checker.check_pattern(name, pd),
+            Node::Bullet(bd) => {
+                checker.check_entity_fields(&bd.definitions, &scope_of(&PATTERN_GLOBALS))
+            }
+            Node::Path(pd) => checker.check_path(name, pd),
+            Node::Function(fd) => checker.check_function(name, fd),
+            _ => {}
+        }
+    }
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    // with what they extend merged in
+    patterns: HashMap<&'a str, PatternData>,
+    paths: HashMap<&'a str, &'a PathData>,
+    functions: HashMap<&'a str, &'a FunctionData>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, message: String, span: Span, label: String) {
+        self.diagnostics
+            .push(Diagnostic::error(message, Some(span)).with_label(label));
+    }
+
+    fn expect(&mut self, field: &str, expected: Expected, expr: &ExpressionType, scope: &Scope) {
+        let t = self.infer(expr, scope);
+        if !expected.accepts(t) {
+            self.error(
+                format!("`{}` should be {}, found {}", field, expected.describe(), t),
+                expr.span,
+                format!("this is {}", t),
+            );
+        }
+    }
+
+    // type of every definition in a block of definitions, which can refer to each other
+    //   inferred quietly, errors are reported once when each definition is checked
+    //   bullet and spawn fields don't see themselves, see field_scope
+    fn definition_scope(&mut self, definitions: &Values, outer: &Scope, fields: bool) -> Scope {
+        let reported = self.diagnostics.len();
+        let mut scope = outer.clone();
+        for name in definitions.keys() {
+            scope.insert(name.clone(), Type::Unknown);
+        }
+        // each pass resolves at least one more level of definitions referring to each other
+        for _ in 0..definitions.len() {
+            let mut changed = false;
+            for (name, expr) in definitions {
+                let t = match fields {
+                    true => self.infer(expr, &field_scope(&scope, outer, name)),
+                    false => self.infer(expr, &scope),
+                };
+                if scope.insert(name.clone(), t) != Some(t) {
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        self.diagnostics.truncate(reported);
+        scope
+    }
+
+    // a pattern sees everything it extends, but only its own definitions are checked here
+    fn check_pattern(&mut self, name: &str, pd: &PatternData) {
+        let inherited = self
+            .patterns
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| pd.clone());
+        let mut scope = scope_of(&PATTERN_GLOBALS);
+        for parameter in parameters(&inherited.arguments) {
+            scope.insert(parameter.to_string(), Type::Unknown);
+        }
+        let scope = self.definition_scope(&inherited.definitions, &scope, false);
+        let iteration_type = match inherited.definitions.get("iteration_type") {
+            Some(ExpressionType {
+                kind: ExpressionKind::Variable(t),
+                ..
+            }) if t == "time" || t == "cycles" || t == "loop" => Some(t.as_str()),
+            Some(expr) if pd.definitions.contains_key("iteration_type") => {
+                self.error(
+                    "`iteration_type` should be one of `time`, `cycles` or `loop`".to_string(),
+                    expr.span,
+                    "unknown iteration type".to_string(),
+                );
+                None
+            }
+            _ => None,
+        };
+        for (name, expr) in in_source_order(&pd.definitions) {
+            match (name.as_str(), iteration_type) {
+                ("iteration_type", _) => {}
+                ("length", Some("time")) => self.expect(name, Expected::Duration, expr, &scope),
+                ("length", Some("cycles")) => self.expect(name, Expected::Int, expr, &scope),
+                ("actions", _) => match &expr.kind {
+                    ExpressionKind::Block(block) => self.check_block(block, &scope),
+                    _ => self.expect(name, Expected::Block, expr, &scope),
+                },
+                _ => {
+                    self.infer(expr, &scope);
+                }
+            }
+        }
+    }
+
+    fn check_path(&mut self, name: &str, pd: &PathData) {
+        // paths see the spawning scope, their parameters, then the per frame inputs
+        let mut scope = scope_of(&PATTERN_GLOBALS);
+        for parameter in parameters(&pd.arguments) {
+            scope.insert(parameter.to_string(), Type::Unknown);
+        }
+        scope.extend(scope_of(&PATH_INPUTS));
+        let scope = self.definition_scope(&pd.definitions, &scope, false);
+        for (field, expr) in in_source_order(&pd.definitions) {
+            if field == "x" || field == "y" {
+                self.expect(field, Expected::Number, expr, &scope);
+            } else {
+                self.infer(expr, &scope);
+            }
+        }
+        for field in ["x", "y"] {
+            if !pd.definitions.contains_key(field) {
+                self.error(
+                    format!("path `{}` has no definition for `{}`", name, field),
+                    pd.span,
+                    format!("`{}` is needed to move along this path", field),
+                );
+            }
+        }
+    }
+
+    // the body of a function is an expression, or assignments followed by a return
+    //   its parameters can be given anything, so their types aren't known
+    fn check_function(&mut self, name: &str, fd: &FunctionData) {
+        let mut scope: Scope = parameters(&fd.arguments)
+            .into_iter()
+            .map(|parameter| (parameter.to_string(), Type::Unknown))
+            .collect();
+        let block = match &fd.body.kind {
+            ExpressionKind::Block(block) => block,
+            _ => {
+                self.infer(&fd.body, &scope);
+                return;
+            }
+        };
+        let mut returns = false;
+        for statement in &block.statements {
+            match statement {
+                Node::Assign(ad) => {
+                    let t = self.infer(&ad.rvalue, &scope);
+                    scope.insert(ad.lvalue.clone(), t);
+                }
+                Node::Return(rd) => {
+                    self.infer(&rd.value, &scope);
+                    returns = true;
+                }
+                other => self.error(
+                    format!("function `{}` can only assign and return", name),
+                    other.span(),
+                    "not allowed in a function".to_string(),
+                ),
+            }
+        }
+        if !returns {
+            self.error(
+                format!("function `{}` never returns a value", name),
+                block.span,
+                "expected `return` somewhere in here".to_string(),
+            );
+        }
+    }
+
+    // bullet and spawn blocks, the fields Entity::from_values reads
+    fn check_entity_fields(&mut self, definitions: &Values, outer: &Scope) {
+        let fields = self.definition_scope(definitions, outer, true);
+        for (name, expr) in in_source_order(definitions) {
+            let scope = field_scope(&fields, outer, name);
+            match entity_field(name) {
+                Some(Expected::Name) => {}
+                Some(Expected::PathCall) => self.check_path_call(name, expr, &scope),
+                Some(Expected::PatternCall) => self.check_behavior(expr, &scope),
+                // evaluated every frame with t, the frames the entity has been alive
+                Some(expected) if name == "speed_over_time" => {
+                    let mut scope = scope.clone();
+                    scope.insert("t".to_string(), Type::Int);
+                    self.expect(name, expected, expr, &scope);
+                }
+                Some(_) if name == "hitbox" => self.check_hitbox(expr, &scope),
+                Some(expected) => {
+                    self.expect(name, expected, expr, &scope);
+                    self.check_option_name(name, expr);
+                }
+                None => {
+                    self.infer(expr, &scope);
+                }
+            }
+        }
+    }
+
+    // `(w, h)`, or the size and offset of the hitbox as `((w, h), (x, y))`
+    fn check_hitbox(&mut self, expr: &ExpressionType, scope: &Scope) {
+        if let ExpressionKind::Vector(parts) = &expr.kind {
+            let reported = self.diagnostics.len();
+            let offset = parts.len() == 2
+                && parts
+                    .iter()
+                    .all(|part| self.infer(part, scope).is_numeric_vec());
+            self.diagnostics.truncate(reported);
+            if offset {
+                for part in parts {
+                    self.expect("hitbox", Expected::NumericVec, part, scope);
+                }
+                return;
+            }
+        }
+        self.expect("hitbox", Expected::NumericVec, expr, scope);
+    }
+
+    // a literal bounds policy, shape or blend mode has to be one the interpreter knows
+    fn check_option_name(&mut self, field: &str, expr: &ExpressionType) {
+        let name = match &expr.kind {
+            ExpressionKind::String(name) => name,
+            _ => return,
+        };
+        let (what, known, expected) = match field {
+            "bounds" => (
+                "bounds policy",
+                BoundsPolicy::from_name(name, 0.0).is_some(),
+                "\"despawn\", \"wrap\", \"bounce\" or \"none\"",
+            ),
+            "shape" => (
+                "shape",
+                HitboxType::from_name(name).is_some(),
+                "\"rectangle\", \"ellipse\" or \"circle\"",
+            ),
+            "blend" => (
+                "blend mode",
+                BlendMode::from_name(name).is_some(),
+                "\"alpha\", \"add\", \"subtract\" or \"multiply\"",
+            ),
+            _ => return,
+        };
+        if !known {
+            self.error(
+                format!("unknown {} `{}`", what, name),
+                expr.span,
+                format!("expected {}", expected),
+            );
+        }
+    }
+
+    fn check_path_call(&mut self, field: &str, expr: &ExpressionType, scope: &Scope) {
+        let (path_name, arguments) = match &expr.kind {
+            ExpressionKind::Expr(ArithmeticExpression::Call(path_name, arguments)) => {
+                (path_name, arguments)
+            }
+            _ => {
+                let t = self.infer(expr, scope);
+                self.error(
+                    format!(
+                        "`{}` should be {}, found {}",
+                        field,
+                        Expected::PathCall.describe(),
+                        t
+                    ),
+                    expr.span,
+                    "not a path call".to_string(),
+                );
+                return;
+            }
+        };
+        for argument in arguments {
+            self.infer(argument, scope);
+        }
+        if let Some(pd) = self.paths.get(path_name.as_str()) {
+            let parameters = parameters(&pd.arguments).len();
+            self.check_arity("path", path_name, parameters, arguments.len(), expr.span);
+        }
+    }
+
+    // paths and patterns called with a different number of arguments than they take
+    fn check_arity(&mut self, what: &str, name: &str, parameters: usize, given: usize, span: Span) {
+        if parameters != given {
+            self.error(
+                format!(
+                    "{} `{}` takes {} argument{} but {} {} given",
+                    what,
+                    name,
+                    parameters,
+                    if parameters == 1 { "" } else { "s" },
+                    given,
+                    if given == 1 { "was" } else { "were" },
+                ),
+                span,
+                format!(
+                    "expected {} argument{}",
+                    parameters,
+                    if parameters == 1 { "" } else { "s" }
+                ),
+            );
+        }
+    }
+
+    fn check_pattern_call(&mut self, cd: &PatternCallData, scope: &Scope) {
+        self.check_pattern_arguments(&cd.pattern, &cd.arguments, cd.span, scope);
+    }
+
+    fn check_behavior(&mut self, expr: &ExpressionType, scope: &Scope) {
+        match &expr.kind {
+            ExpressionKind::Variable(pattern) => {
+                self.check_pattern_arguments(pattern, &[], expr.span, scope)
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Call(pattern, arguments)) => {
+                self.check_pattern_arguments(pattern, arguments, expr.span, scope)
+            }
+            _ => {
+                let t = self.infer(expr, scope);
+                self.error(
+                    format!(
+                        "`behavior` should be {}, found {}",
+                        Expected::PatternCall.describe(),
+                        t
+                    ),
+                    expr.span,
+                    "not a pattern".to_string(),
+                );
+            }
+        }
+    }
+
+    fn check_pattern_arguments(
+        &mut self,
+        pattern: &str,
+        arguments: &[ExpressionType],
+        span: Span,
+        scope: &Scope,
+    ) {
+        for argument in arguments {
+            self.infer(argument, scope);
+        }
+        if let Some(pd) = self.patterns.get(pattern) {
+            let parameters = parameters(&pd.arguments).len();
+            self.check_arity("pattern", pattern, parameters, arguments.len(), span);
+        }
+    }
+
+    fn check_block(&mut self, block: &Block, scope: &Scope) {
+        let mut scope = scope.clone();
+        for statement in &block.statements {
+            match statement {
+                Node::Assign(ad) => {
+                    let t = self.infer(&ad.rvalue, &scope);
+                    scope.insert(ad.lvalue.clone(), t);
+                }
+                Node::Spawn(sd) => self.check_entity_fields(&sd.definitions, &scope),
+                Node::Wait(wd) => self.check_wait(wd, &scope),
+                Node::Run(cd) | Node::Fire(cd) => self.check_pattern_call(cd, &scope),
+                Node::Return(rd) => {
+                    self.infer(&rd.value, &scope);
+                    self.error(
+                        "`return` outside of a function".to_string(),
+                        rd.span,
+                        "only functions defined with `fn` can return".to_string(),
+                    );
+                }
+                Node::For(fd) => {
+                    let mut inner = scope.clone();
+                    for name in fd.initial_definitions.keys() {
+                        inner.insert(name.clone(), Type::Int);
+                    }
+                    if let Condition::When(e) | Condition::Unless(e) = &fd.condition {
+                        let t = self.infer(e, &inner);
+                        if !matches!(t, Type::Bool | Type::Unknown) {
+                            self.error(
+                                format!("`for` condition should be a bool, found {}", t),
+                                e.span,
+                                format!("this is {}", t),
+                            );
+                        }
+                    }
+                    self.check_block(&fd.body, &inner);
+                }
+                Node::Head(_)
+                | Node::Pattern(_)
+                | Node::Bullet(_)
+                | Node::Path(_)
+                | Node::Function(_) => {}
+            }
+        }
+    }
+
+    fn check_wait(&mut self, wd: &WaitData, scope: &Scope) {
+        let (expr, expected, unit) = match wd {
+            WaitData::Frames(e) => (e, Expected::Int, "frames"),
+            WaitData::Time(e) => (e, Expected::Number, "seconds"),
+        };
+        let t = self.infer(expr, scope);
+        if !expected.accepts(t) {
+            self.error(
+                format!(
+                    "a wait in {} should be {}, found {}",
+                    unit,
+                    expected.describe(),
+                    t
+                ),
+                expr.span,
+                format!("this is {}", t),
+            );
+        }
+    }
+
+    fn infer(&mut self, expr: &ExpressionType, scope: &Scope) -> Type {
+        match &expr.kind {
+            ExpressionKind::Int(_) => Type::Int,
+            ExpressionKind::Float(_) => Type::Float,
+            ExpressionKind::String(_) => Type::String,
+            ExpressionKind::Range(..) => Type::Range,
+            ExpressionKind::Block(_) => Type::Block,
+            ExpressionKind::Duration(wd) => {
+                self.check_wait(wd, scope);
+                Type::Duration
+            }
+            ExpressionKind::None => Type::Unknown,
+            ExpressionKind::Variable(name) => match scope.get(name) {
+                Some(t) => *t,
+                None if ps_funcs::constant(name).is_some() => Type::Float,
+                None => Type::Unknown,
+            },
+            ExpressionKind::Vector(elements) => {
+                let types: Vec<Type> = elements.iter().map(|e| self.infer(e, scope)).collect();
+                self.vector_type(&types, expr.span)
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Unary(UnaryOperator::Negate, e)) => {
+                match self.infer(e, scope) {
+                    t @ (Type::Int | Type::Float | Type::Unknown) => t,
+                    t => {
+                        self.error(
+                            format!("cannot negate {}", t),
+                            e.span,
+                            format!("this is {}", t),
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Call(name, arguments)) => {
+                let types: Vec<Type> = arguments.iter().map(|a| self.infer(a, scope)).collect();
+                // what a function returns depends on what it's given, so it isn't followed
+                //   paths and unknown functions are left to name resolution
+                let arity = match (ps_funcs::arity(name), self.functions.get(name.as_str())) {
+                    (Some(arity), _) => arity,
+                    (None, Some(fd)) => {
+                        let parameters = parameters(&fd.arguments).len();
+                        (parameters, parameters)
+                    }
+                    (None, None) => return Type::Unknown,
+                };
+                if types.len() < arity.0 || types.len() > arity.1 {
+                    self.error(
+                        format!(
+                            "`{}` takes {} arguments, found {}",
+                            name,
+                            ps_funcs::describe_arity(arity),
+                            types.len()
+                        ),
+                        expr.span,
+                        "wrong number of arguments".to_string(),
+                    );
+                    return Type::Unknown;
+                }
+                if ps_funcs::arity(name).is_none() {
+                    return Type::Unknown;
+                }
+                match builtin_type(name, &types) {
+                    Ok(result) => result,
+                    Err(expected) => {
+                        let found = describe_types(&types);
+                        self.error(
+                            format!("`{}` expects {}, found {}", name, expected, found),
+                            match (arguments.first(), arguments.last()) {
+                                (Some(first), Some(last)) => first.span.to(last.span),
+                                _ => expr.span,
+                            },
+                            format!("this is {}", found),
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Binary(op, lhs, rhs)) => {
+                let (l, r) = (self.infer(lhs, scope), self.infer(rhs, scope));
+                match binary_type(op, l, r) {
+                    Some(t) => t,
+                    None => {
+                        self.error(
+                            format!("operator `{}` is not defined for {} and {}", op, l, r),
+                            lhs.span.to(rhs.span),
+                            format!("{} {} {}", l, op, r),
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+        }
+    }
+
+    // vectors hold all ints, numbers (promoted to floats), or strings
+    fn vector_type(&mut self, types: &[Type], span: Span) -> Type {
+        if types.contains(&Type::Unknown) {
+            Type::Unknown
+        } else if types.iter().all(|t| *t == Type::Int) {
+            Type::IntVec
+        } else if types.iter().all(|t| t.is_numeric()) {
+            Type::FloatVec
+        } else if types.iter().all(|t| *t == Type::String) {
+            Type::StrVec
+        } else {
+            let first = types[0];
+            let other = types
+                .iter()
+                .find(|t| **t != first)
+                .copied()
+                .unwrap_or(first);
+            self.error(
+                "vector elements should all be numbers or all be strings".to_string(),
+                span,
+                format!("found {} and {}", first, other),
+            );
+            Type::Unknown
+        }
+    }
+}
+
+// result of a binary operator, mirroring evaluate::binary_op; None if it isn't defined
+fn binary_type(op: &Op, l: Type, r: Type) -> Option<Type> {
+    use Type::*;
+    let boolean = matches!(
+        op,
+        Op::Test | Op::And | Op::Or | Op::GT | Op::LT | Op::GTE | Op::LTE
+    );
+    if l == Unknown || r == Unknown {
+        return Some(if boolean { Bool } else { Unknown });
+    }
+    match op {
+        Op::Add | Op::Sub | Op::Mul | Op::Div => match (l, r) {
+            (Int, Int) => Some(Int),
+            (l, r) if l.is_numeric() && r.is_numeric() => Some(Float),
+            (String, String) if *op == Op::Add => Some(String),
+            (IntVec, IntVec) if matches!(op, Op::Add | Op::Sub) => Some(IntVec),
+            (l, r) if l.is_numeric_vec() && r.is_numeric_vec() => Some(FloatVec),
+            (l, r) if *op == Op::Mul && l.is_numeric() && r.is_numeric_vec() => Some(FloatVec),
+            (l, r) if matches!(op, Op::Mul | Op::Div) && l.is_numeric_vec() && r.is_numeric() => {
+                Some(FloatVec)
+            }
+            _ => None,
+        },
+        Op::Exp => match (l, r) {
+            (Int, Int) => Some(Int),
+            (l, r) if l.is_numeric() && r.is_numeric() => Some(Float),
+            _ => None,
+        },
+        Op::And | Op::Or => (l == Bool && r == Bool).then_some(Bool),
+        Op::Test => {
+            ((l.is_numeric() && r.is_numeric()) || (l == Bool && r == Bool)).then_some(Bool)
+        }
+        Op::GT | Op::LT | Op::GTE | Op::LTE => (l.is_numeric() && r.is_numeric()).then_some(Bool),
+    }
+}
+
+// result of a builtin on arguments of these types, mirroring ps_funcs
+//   the arity has already been checked; Err with what the arguments should have been
+fn builtin_type(name: &str, types: &[Type]) -> Result<Type, &'static str> {
+    use Type::*;
+    let numeric = |t: &Type| t.is_numeric() || *t == Unknown;
+    let all_numeric = types.iter().all(numeric);
+    let numeric_vec = |t: &Type| t.is_numeric_vec() || *t == Unknown;
+    // one vector, or its elements passed one by one
+    let vector_argument = |types: &[Type]| match types {
+        [t] => numeric_vec(t),
+        _ => all_numeric,
+    };
+    // the type ints and floats promote to together
+    let promoted = |types: &[Type]| {
+        if types.contains(&Unknown) {
+            Unknown
+        } else if types.iter().all(|t| *t == Int) {
+            Int
+        } else {
+            Float
+        }
+    };
+    match name {
+        "sqrt" | "sin" | "cos" | "tan" | "asin" | "acos" | "atan2" | "exp" | "ln" | "lerp"
+        | "smoothstep" | "rand_range" => all_numeric.then_some(Float).ok_or("numbers"),
+        "rand" | "rand_angle" => Ok(Float),
+        "abs" | "sign" => all_numeric.then_some(types[0]).ok_or("a number"),
+        "floor" | "ceil" | "round" => all_numeric.then_some(Int).ok_or("a number"),
+        "rand_int" => types
+            .iter()
+            .all(|t| matches!(t, Int | Unknown))
+            .then_some(Int)
+            .ok_or("two ints"),
+        "rand_in_circle" => all_numeric.then_some(FloatVec).ok_or("a number"),
+        "from_angle" => all_numeric.then_some(FloatVec).ok_or("numbers"),
+        "dot" | "cross2" | "distance" => types
+            .iter()
+            .all(numeric_vec)
+            .then_some(Float)
+            .ok_or("two vectors"),
+        "rotate" => match types {
+            [v, a] if numeric_vec(v) && numeric(a) => Ok(FloatVec),
+            _ => Err("a vector and an angle"),
+        },
+        // gathered like x and y
+        "length" | "angle_of" => vector_argument(types).then_some(Float).ok_or("a vector"),
+        "normalize" => vector_argument(types).then_some(FloatVec).ok_or("a vector"),
+        // a single vector is spread into its elements
+        "min" | "max" if types.len() == 1 && types[0].is_numeric_vec() => {
+            Ok(if types[0] == IntVec { Int } else { Float })
+        }
+        "min" | "max" | "clamp" | "mod" | "ping_pong" | "repeat" => {
+            all_numeric.then(|| promoted(types)).ok_or("numbers")
+        }
+        // curves give a point of the same kind as their control points
+        "bezier" | "spline" => match types.split_last() {
+            Some((u, _)) if !numeric(u) => Err("control points and a number"),
+            // spline(points, u) through a vector of numbers
+            Some((_, [points])) if name == "spline" && numeric_vec(points) => Ok(Float),
+            Some((_, points)) if points.iter().all(|t| *t == Unknown) => Ok(Unknown),
+            Some((_, points)) if points.iter().all(numeric) => Ok(Float),
+            Some((_, points)) if points.iter().all(numeric_vec) => Ok(FloatVec),
+            _ => Err("control points that are all numbers or all vectors"),
+        },
+        easing if ps_funcs::arity(easing) == Some((1, 1)) && easing.starts_with("ease_") => {
+            all_numeric.then_some(Float).ok_or("a number")
+        }
+        // and separate arguments gathered into one vector
+        "x" | "y" | "rand_choice" => match types {
+            [IntVec] => Ok(Int),
+            [FloatVec] => Ok(Float),
+            [StrVec] => Ok(String),
+            [Unknown] => Ok(Unknown),
+            [_] => Err("a vector"),
+            _ if all_numeric => Ok(promoted(types)),
+            _ if types.iter().all(|t| matches!(t, String | Unknown)) => Ok(String),
+            _ => Err("all numbers or all strings"),
+        },
+        _ => Ok(Unknown),
+    }
+}
+
+// one type, or a list of them for several arguments
+fn describe_types(types: &[Type]) -> std::string::String {
+    match types {
+        [t] => t.to_string(),
+        _ => format!(
+            "({})",
+            types
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::Parser;
+
+    // each diagnostic as its message, the source it points at and the line it's on
+    fn diagnose(source: &str) -> Vec<(std::string::String, &str, usize)> {
+        let head = match Parser::parse_from_string(source.to_string()) {
+            Ok(Node::Head(head)) => head,
+            other => panic!("didn't parse: {:?}", other.err()),
+        };
+        check(&head)
+            .into_iter()
+            .map(|d| {
+                let span = d.span.expect("type errors point into the source");
+                (d.message, &source[span.start..span.end], span.line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn operator_types() {
+        let source = "pattern p = {\n actions = {\n  name = \"ring\";\n  x = name * 2;\n }\n}";
+        assert_eq!(
+            diagnose(source),
+            [(
+                "operator `*` is not defined for string and int".into(),
+                "name * 2",
+                4
+            )]
+        );
+    }
+
+    #[test]
+    fn conditions_are_bools() {
+        let source = "pattern p = {
+ actions = {
+  for (i = 0...3) when (i + 1) {
+   wait 1 frames;
+  }
+  for (i = 0...3) unless (i == 1 and i > 0) {
+   wait 1 frames;
+  }
+ }
+}";
+        assert_eq!(
+            diagnose(source),
+            [(
+                "`for` condition should be a bool, found int".into(),
+                "i + 1",
+                3
+            )]
+        );
+    }
+
+    #[test]
+    fn call_arity() {
+        let source = "path curve(t, speed) = {
+ x = t * speed;
+ y = 0;
+}
+pattern ring(count, spin) = {
+ actions = { wait 1 frames; }
+}
+pattern p = {
+ actions = {
+  spawn { position_fn = curve(t); }
+  spawn { position_fn = curve(t, 2); }
+  run ring(8);
+  fire ring(8, 0, 1);
+  run ring(8, 0);
+ }
+}";
+        assert_eq!(
+            diagnose(source),
+            [
+                (
+                    "path `curve` takes 2 arguments but 1 was given".into(),
+                    "curve(t)",
+                    10
+                ),
+                (
+                    "pattern `ring` takes 2 arguments but 1 was given".into(),
+                    "run ring(8);",
+                    12
+                ),
+                (
+                    "pattern `ring` takes 2 arguments but 3 were given".into(),
+                    "fire ring(8, 0, 1);",
+                    13
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn option_names() {
+        let source = "bullet b = {
+ bounds = \"wrapp\";
+ shape = \"square\";
+ blend = \"screen\";
+}
+bullet fine = {
+ bounds = \"bounce\";
+ shape = \"circle\";
+ blend = \"add\";
+ hitbox = ((4, 2), (1, 0));
+}";
+        assert_eq!(
+            diagnose(source),
+            [
+                ("unknown bounds policy `wrapp`".into(), "\"wrapp\"", 2),
+                ("unknown shape `square`".into(), "\"square\"", 3),
+                ("unknown blend mode `screen`".into(), "\"screen\"", 4),
+            ]
+        );
+    }
+}
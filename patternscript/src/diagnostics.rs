@@ -0,0 +1,248 @@
+//
+// structured errors/warnings and rustc-style rendering of them against the source
+//
+
+use crate::interpreter::error::RuntimeError;
+use crate::parser::lexer::TokenKind;
+use crate::parser::parser::ParseError;
+use crate::parser::types::Span;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    // no span for errors that don't originate in the source, such as io errors
+    pub span: Option<Span>,
+    // short text printed next to the caret underline
+    pub label: Option<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Option<Span>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            span,
+            label: None,
+            help: None,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Option<Span>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(message, span)
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Builds a diagnostic from any error produced by the parser or interpreter,
+    /// falling back to the error message without a location for anything else.
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        if let Some(pe) = error.downcast_ref::<ParseError>() {
+            return pe.into();
+        }
+        if let Some(re) = error.downcast_ref::<RuntimeError>() {
+            return re.into();
+        }
+        Diagnostic::error(error.to_string(), None)
+    }
+
+    /// Renders the diagnostic in the style of rustc:
+    ///
+    /// ```text
+    /// error: expected `;` or `}` after expression, found number `2`
+    ///  --> examples/1.pattern:2:9
+    ///   |
+    /// 2 |   a = 1 2;
+    ///   |         ^ expected `;` or `}` after expression
+    /// ```
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let mut out = format!("{}: {}\n", self.severity, self.message);
+        let span = match self.span {
+            Some(span) => span,
+            None => {
+                if let Some(help) = &self.help {
+                    out.push_str(&format!("  = help: {}\n", help));
+                }
+                return out;
+            }
+        };
+
+        let line = source
+            .lines()
+            .nth(span.line.saturating_sub(1))
+            .unwrap_or("");
+        let gutter = " ".repeat(span.line.to_string().len());
+        out.push_str(&format!(
+            "{}--> {}:{}:{}\n",
+            gutter, file_name, span.line, span.column
+        ));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", span.line, line));
+
+        // keep tabs in the caret prefix so the underline lines up with the source
+        let prefix: String = line
+            .chars()
+            .take(span.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let remaining = line.chars().count().saturating_sub(prefix.chars().count());
+        let width = source
+            .get(span.start..span.end)
+            .map(|s| s.chars().take_while(|c| *c != '\n').count())
+            .unwrap_or(1)
+            .clamp(1, remaining.max(1));
+        out.push_str(&format!("{} | {}{}", gutter, prefix, "^".repeat(width)));
+        if let Some(label) = &self.label {
+            out.push_str(&format!(" {}", label));
+        }
+        out.push('\n');
+
+        if let Some(help) = &self.help {
+            out.push_str(&format!("{} |\n", gutter));
+            out.push_str(&format!("{} = help: {}\n", gutter, help));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{}: {}: {}", span, self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let span = Some(error.span());
+        match error {
+            ParseError::Token(t) => Diagnostic::error(format!("unexpected {}", t.kind), span)
+                .with_label("unexpected token")
+                .with_help(match t.kind {
+                    TokenKind::Keyword(_) => "`for`, `wait` and `spawn` are only allowed inside blocks; top level definitions start with `pattern`, `bullet`, `path` or `fn`",
+                    _ => "this token can't start a statement here",
+                }),
+            ParseError::Expected(expected, found) => Diagnostic::error(
+                format!("expected {}, found {}", expected, found.kind),
+                span,
+            )
+            .with_label(format!("expected {}", expected)),
+            ParseError::ExpectedDescribed(expected, found) => Diagnostic::error(
+                format!("expected {}, found {}", expected, found.kind),
+                span,
+            )
+            .with_label(format!("expected {}", expected)),
+            ParseError::EOF(_) => Diagnostic::error("unexpected end of file", span)
+                .with_label("file ends here")
+                .with_help("check for an unclosed `{` or `(`"),
+            ParseError::Definitions(_) => {
+                Diagnostic::error("only definitions are allowed in this block", span)
+                    .with_label("expected `name = value;`")
+                    .with_help("`bullet` and `path` blocks can't contain `for`, `wait` or `spawn`")
+            }
+            ParseError::InvalidNumber(_) => Diagnostic::error("invalid number", span)
+                .with_label("not a valid int or float"),
+            ParseError::InvalidForDef(_) => Diagnostic::error("invalid for definition", span)
+                .with_label("expected a loop variable")
+                .with_help("for definitions must be in the form of `(a = 0...3, b = 0...3)`"),
+            ParseError::RangeMustBeInt(_) => Diagnostic::error("range bounds must be ints", span)
+                .with_label("expected `int...int`")
+                .with_help("floats can't be used as range bounds, try `0...10`"),
+            ParseError::BadVecElement(_) => Diagnostic::error("bad vector element", span)
+                .with_label("expected `,` or `)`"),
+            ParseError::TimeTypeError(_) => Diagnostic::error("invalid duration", span)
+                .with_label("wrong type for a duration")
+                .with_help("frames must be integers, seconds must be numeric"),
+            ParseError::NeedsClearerError(message, _) => Diagnostic::error(*message, span),
+            ParseError::Redefinition(name, _, first) => {
+                Diagnostic::error(format!("`{}` is defined twice", name), span)
+                    .with_label("defined again here")
+                    .with_help(format!(
+                        "`{}` was first defined at {}; patterns, bullets, paths and functions all need different names",
+                        name, first
+                    ))
+            }
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        // the display message is prefixed with its location, which render already shows
+        let message = error.to_string();
+        let message = match error.span() {
+            Some(span) => message
+                .strip_prefix(&format!("{}: ", span))
+                .unwrap_or(&message)
+                .to_string(),
+            None => message,
+        };
+        let diagnostic = Diagnostic::error(message, error.span());
+        match error {
+            RuntimeError::VarNotDef(name, _) => {
+                Diagnostic::error(format!("variable not defined: `{}`", name), error.span())
+                    .with_label("not found in this scope")
+            }
+            RuntimeError::OperatorTypeError(op, l, r, _) => Diagnostic::error(
+                format!("operator `{}` not defined for {:?} and {:?}", op, l, r),
+                error.span(),
+            )
+            .with_label("type error"),
+            RuntimeError::CondNotBoolError(_) => diagnostic
+                .with_label("expected bool")
+                .with_help("conditions are comparisons such as `i == 1` joined with `and`/`or`"),
+            _ => diagnostic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parser::Parser;
+
+    // carets line up under tabs and multibyte characters the same way the source shows them
+    #[test]
+    fn renders_like_the_expected_output() {
+        let source = include_str!("../../examples/errors.pattern");
+        let diagnostics = match Parser::parse_with_diagnostics(source.to_string()) {
+            Err(diagnostics) => diagnostics,
+            Ok(_) => panic!("examples/errors.pattern is meant to be broken"),
+        };
+        // as the cli prints them, a blank line after each
+        let rendered: String = diagnostics
+            .iter()
+            .map(|d| d.render("examples/errors.pattern", source) + "\n")
+            .collect();
+        assert_eq!(rendered, include_str!("../../examples/errors.expected"));
+    }
+}
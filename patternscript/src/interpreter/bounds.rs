@@ -0,0 +1,102 @@
+use super::entity::{BoundsPolicy, ExecutionEnvironment};
+use cgmath::{Angle, Deg, Vector2};
+
+// the visible area bullets are kept to, set by the host
+#[derive(Debug, Clone, Copy)]
+pub struct Playfield {
+    pub min: Vector2<f64>,
+    pub max: Vector2<f64>,
+}
+
+impl Playfield {
+    pub fn new(min: Vector2<f64>, max: Vector2<f64>) -> Self {
+        Playfield { min, max }
+    }
+
+    // a playfield from the origin to width, height
+    pub fn from_size(width: f64, height: f64) -> Self {
+        Playfield::new(Vector2::new(0.0, 0.0), Vector2::new(width, height))
+    }
+
+    pub fn contains(&self, point: Vector2<f64>, margin: f64) -> bool {
+        point.x >= self.min.x - margin
+            && point.x <= self.max.x + margin
+            && point.y >= self.min.y - margin
+            && point.y <= self.max.y + margin
+    }
+}
+
+// wrap v into [min, max) when it's outside of it
+fn wrap(v: f64, min: f64, max: f64) -> f64 {
+    let width = max - min;
+    if width <= 0.0 || (v >= min && v < max) {
+        v
+    } else {
+        min + (v - min).rem_euclid(width)
+    }
+}
+
+// mirror v back inside [min, max], whether it was mirrored
+fn reflect(v: f64, min: f64, max: f64) -> (f64, bool) {
+    if v < min {
+        ((2.0 * min - v).min(max), true)
+    } else if v > max {
+        ((2.0 * max - v).max(min), true)
+    } else {
+        (v, false)
+    }
+}
+
+/// Applies each entity's bounds policy after it has moved, returning the (sorted) indices
+/// of entities that left the playfield and should be despawned.
+///
+/// Entities placed by a `position_fn` are put back by their path every frame, so they
+/// can only be despawned; wrapping and bouncing them would be undone on the next move.
+/// Entities moved by a `velocity_fn` bounce by mirroring what their path gives afterwards.
+pub fn confine(entities: &mut [ExecutionEnvironment], playfield: &Playfield) -> Vec<usize> {
+    let mut escaped: Vec<usize> = Vec::new();
+    for (i, environment) in entities.iter_mut().enumerate() {
+        let entity = &mut environment.entity;
+        match entity.bounds {
+            BoundsPolicy::Unbounded => {}
+            BoundsPolicy::Despawn { margin } => {
+                if !playfield.contains(entity.position, margin) {
+                    escaped.push(i);
+                }
+            }
+            _ if entity.position_fn.is_some() => {}
+            BoundsPolicy::Wrap { margin } => {
+                if playfield.contains(entity.position, margin) {
+                    continue;
+                }
+                // the margin is room to leave the screen entirely before coming back
+                let (min, max) = (playfield.min, playfield.max);
+                entity.position = Vector2::new(
+                    wrap(entity.position.x, min.x - margin, max.x + margin),
+                    wrap(entity.position.y, min.y - margin, max.y + margin),
+                );
+                // it teleported, so there's nothing in between to sweep across
+                environment.previous_position = entity.position;
+            }
+            BoundsPolicy::Bounce => {
+                let (min, max) = (playfield.min, playfield.max);
+                let (x, bounced_x) = reflect(entity.position.x, min.x, max.x);
+                let (y, bounced_y) = reflect(entity.position.y, min.y, max.y);
+                entity.position = Vector2::new(x, y);
+                // flip whatever the velocity comes from on the next move, a velocity_fn
+                // recomputes it every frame so its output is mirrored from now on instead
+                if bounced_x {
+                    entity.velocity.x = -entity.velocity.x;
+                    entity.rotation = (Deg(180.0) - entity.rotation).normalize();
+                    environment.reflection.x = -environment.reflection.x;
+                }
+                if bounced_y {
+                    entity.velocity.y = -entity.velocity.y;
+                    entity.rotation = (-entity.rotation).normalize();
+                    environment.reflection.y = -environment.reflection.y;
+                }
+            }
+        }
+    }
+    escaped
+}
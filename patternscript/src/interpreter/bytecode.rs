@@ -0,0 +1,275 @@
+use super::error::RuntimeError;
+use super::primitive::Primitive;
+use super::ps_funcs;
+use super::vm;
+use crate::parser::parser::{
+    ArithmeticExpression, ExpressionKind, ExpressionType, UnaryOperator, Values,
+};
+use crate::parser::types::{Op, Span};
+use anyhow::Result;
+
+// inputs a path function is run with every frame, in slot order
+pub const PATH_INPUTS: [&str; 5] = [
+    "t",
+    "towards_player",
+    "player_position",
+    "player_velocity",
+    "distance_to_player",
+];
+
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    // push a value from the program's constant pool
+    Constant(usize),
+    // push the input in this slot
+    Load(usize),
+    Negate,
+    Binary(Op),
+    // pop this many arguments and push the builtin applied to them
+    Call(String, usize),
+    // pop this many elements into a vector, the first pushed being the first element
+    Vector(usize),
+}
+
+// an expression lowered to run on the vm
+//   variables bound in the environment it was compiled against are inlined, so the only names
+//   left are the input slots supplied on every run and nothing is looked up in a Values map
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub code: Vec<Instruction>,
+    // the expression each instruction came from, for errors
+    pub spans: Vec<Span>,
+    pub constants: Vec<Primitive>,
+}
+
+impl Program {
+    fn new() -> Self {
+        Program {
+            code: Vec::new(),
+            spans: Vec::new(),
+            constants: Vec::new(),
+        }
+    }
+
+    // whether running it twice on the same inputs gives the same result
+    pub fn is_pure(&self) -> bool {
+        is_pure(&self.code)
+    }
+}
+
+fn is_pure(code: &[Instruction]) -> bool {
+    code.iter().all(|i| match i {
+        Instruction::Call(fn_name, _) => ps_funcs::is_pure(fn_name),
+        _ => true,
+    })
+}
+
+// x and y of a path, compiled against the arguments and locals of one entity
+#[derive(Debug, Clone)]
+pub struct PathProgram {
+    pub x: Program,
+    pub y: Program,
+}
+
+impl PathProgram {
+    pub fn compile(env: &Values) -> Result<Self> {
+        Ok(PathProgram {
+            x: compile(&PathProgram::x_expr(), env, &PATH_INPUTS)?,
+            y: compile(&PathProgram::y_expr(), env, &PATH_INPUTS)?,
+        })
+    }
+
+    pub fn is_pure(&self) -> bool {
+        self.x.is_pure() && self.y.is_pure()
+    }
+
+    pub fn x_expr() -> ExpressionType {
+        ExpressionKind::Variable("x".to_string()).into()
+    }
+
+    pub fn y_expr() -> ExpressionType {
+        ExpressionKind::Variable("y".to_string()).into()
+    }
+}
+
+/// Compiles `expr` against `env`. Names in `slots` are left as inputs given to
+/// `vm::execute` in the same order; every other variable has to be bound in `env`.
+pub fn compile(expr: &ExpressionType, env: &Values, slots: &[&str]) -> Result<Program> {
+    let mut compiler = Compiler {
+        env,
+        slots,
+        program: Program::new(),
+        inlining: Vec::new(),
+    };
+    compiler.expression(expr)?;
+    Ok(compiler.program)
+}
+
+struct Compiler<'a> {
+    env: &'a Values,
+    slots: &'a [&'a str],
+    program: Program,
+    // bindings being inlined right now, to catch definitions that refer to themselves
+    inlining: Vec<String>,
+}
+
+impl Compiler<'_> {
+    fn emit(&mut self, instruction: Instruction, span: Span) {
+        self.program.code.push(instruction);
+        self.program.spans.push(span);
+    }
+
+    fn constant(&mut self, value: Primitive, span: Span) {
+        self.program.constants.push(value);
+        self.emit(
+            Instruction::Constant(self.program.constants.len() - 1),
+            span,
+        );
+    }
+
+    fn expression(&mut self, expr: &ExpressionType) -> Result<()> {
+        let code_start = self.program.code.len();
+        let constants_start = self.program.constants.len();
+        let span = expr.span;
+        match &expr.kind {
+            ExpressionKind::Int(i) => self.constant(Primitive::I64(*i), span),
+            ExpressionKind::Float(f) => self.constant(Primitive::F64(*f), span),
+            ExpressionKind::String(s) => self.constant(Primitive::String(s.clone()), span),
+            ExpressionKind::Variable(var) => {
+                if let Some(slot) = self.slots.iter().position(|s| s == var) {
+                    self.emit(Instruction::Load(slot), span);
+                } else if let Some(bound) = self.env.get(var) {
+                    if self.inlining.contains(var) {
+                        return Err(RuntimeError::RecursiveDefinition(var.clone(), span).into());
+                    }
+                    self.inlining.push(var.clone());
+                    self.expression(bound)?;
+                    self.inlining.pop();
+                } else if let Some(c) = ps_funcs::constant(var) {
+                    self.constant(Primitive::F64(c), span);
+                } else {
+                    return Err(RuntimeError::VarNotDef(var.clone(), span).into());
+                }
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Unary(op, arg)) => match op {
+                UnaryOperator::Negate => {
+                    self.expression(arg)?;
+                    self.emit(Instruction::Negate, arg.span)
+                }
+            },
+            ExpressionKind::Expr(ArithmeticExpression::Call(fn_name, args)) => {
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                self.emit(Instruction::Call(fn_name.clone(), args.len()), span)
+            }
+            ExpressionKind::Expr(ArithmeticExpression::Binary(op, lhs, rhs)) => {
+                self.expression(lhs)?;
+                self.expression(rhs)?;
+                self.emit(Instruction::Binary(op.clone()), lhs.span.to(rhs.span));
+            }
+            ExpressionKind::Vector(elements) => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+                self.emit(Instruction::Vector(elements.len()), span);
+            }
+            ExpressionKind::Block(_)
+            | ExpressionKind::Duration(_)
+            | ExpressionKind::Range(..)
+            | ExpressionKind::None => {
+                return Err(RuntimeError::ComputeTypeError(span).into());
+            }
+        }
+        self.fold(code_start, constants_start, span);
+        Ok(())
+    }
+
+    // code that doesn't read any input gives the same value every run, so run it once now
+    //   constants from constants_start on were only pushed for this code and are replaced too
+    //   errors are left in, to be raised when the program runs like the tree walker would
+    //   random builtins are left in too, they have to give a new value every run
+    fn fold(&mut self, code_start: usize, constants_start: usize, span: Span) {
+        let code = &self.program.code[code_start..];
+        if code.len() < 2
+            || code.iter().any(|i| matches!(i, Instruction::Load(_)))
+            || !is_pure(code)
+        {
+            return;
+        }
+        if let Ok(value) = vm::run(&self.program, code_start, &[]) {
+            self.program.code.truncate(code_start);
+            self.program.spans.truncate(code_start);
+            self.program.constants.truncate(constants_start);
+            self.constant(value, span);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::Op;
+
+    fn int(i: i64) -> ExpressionType {
+        ExpressionKind::Int(i).into()
+    }
+
+    fn variable(name: &str) -> ExpressionType {
+        ExpressionKind::Variable(name.to_string()).into()
+    }
+
+    fn add(lhs: ExpressionType, rhs: ExpressionType) -> ExpressionType {
+        ExpressionKind::Expr(ArithmeticExpression::Binary(
+            Op::Add,
+            Box::new(lhs),
+            Box::new(rhs),
+        ))
+        .into()
+    }
+
+    fn call(name: &str, arguments: Vec<ExpressionType>) -> ExpressionType {
+        ExpressionKind::Expr(ArithmeticExpression::Call(name.to_string(), arguments)).into()
+    }
+
+    #[test]
+    fn folds_what_reads_no_input() {
+        let mut env = Values::new();
+        env.insert("a".to_string(), add(int(1), int(2)));
+        let program = compile(&add(variable("a"), int(4)), &env, &PATH_INPUTS).unwrap();
+        assert_eq!(program.code.len(), 1);
+        assert_eq!(vm::execute(&program, &[]).unwrap(), Primitive::I64(7));
+
+        let program = compile(&add(variable("t"), add(int(1), int(2))), &env, &PATH_INPUTS);
+        let code = program.unwrap().code;
+        assert!(matches!(
+            code.as_slice(),
+            [
+                Instruction::Load(0),
+                Instruction::Constant(_),
+                Instruction::Binary(Op::Add)
+            ]
+        ));
+    }
+
+    #[test]
+    fn leaves_random_builtins_and_errors_in() {
+        let program = compile(&call("rand", vec![]), &Values::new(), &[]).unwrap();
+        assert!(!program.is_pure());
+        assert!(matches!(program.code.as_slice(), [Instruction::Call(..)]));
+
+        let program = compile(&call("sin", vec![int(1), int(2)]), &Values::new(), &[]).unwrap();
+        assert!(program.is_pure());
+        assert!(vm::execute(&program, &[]).is_err());
+    }
+
+    #[test]
+    fn unbound_and_recursive_variables_fail_to_compile() {
+        assert!(compile(&variable("nowhere"), &Values::new(), &[]).is_err());
+        let mut env = Values::new();
+        env.insert("a".to_string(), add(variable("b"), int(1)));
+        env.insert("b".to_string(), add(variable("a"), int(1)));
+        assert!(compile(&variable("a"), &env, &[]).is_err());
+        assert!(compile(&variable("pi"), &env, &[]).is_ok());
+    }
+}
@@ -0,0 +1,402 @@
+use super::entity::{Entity, ExecutionEnvironment, Hitbox, HitboxType};
+use super::spatial::SpatialHash;
+use super::target::{TargetId, TargetMap};
+use cgmath::{InnerSpace, Rad, Vector2, Zero};
+
+// a bullet touching a target's hurtbox during a step
+#[derive(Debug, Clone)]
+pub struct Hit {
+    // index into Interpreter::entities after the step, None once the entity is despawned
+    pub entity: Option<usize>,
+    // the entity as it was when it hit, kept when despawning removes it from the interpreter
+    pub despawned: Option<Entity>,
+    pub target: TargetId,
+    // where the entity was when it hit
+    pub position: Vector2<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CollisionOptions {
+    // remove entities from the interpreter as soon as they hit something
+    pub despawn_on_hit: bool,
+    // test the whole distance moved in a frame, so fast bullets can't skip over a hurtbox
+    pub swept: bool,
+}
+
+// a hitbox placed in the world
+//   rectangles and ellipses are size wide and tall, circles are size.x wide
+//   sweep is the distance back to where the shape was on the previous frame
+#[derive(Debug, Clone, Copy)]
+struct Shape {
+    center: Vector2<f64>,
+    half: Vector2<f64>,
+    // cos and sin of the rotation
+    rotation: (f64, f64),
+    kind: ShapeKind,
+    sweep: Vector2<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ShapeKind {
+    Rectangle,
+    Ellipse,
+}
+
+fn rotate(v: Vector2<f64>, (cos, sin): (f64, f64)) -> Vector2<f64> {
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+impl Shape {
+    fn new(hitbox: &Hitbox, position: Vector2<f64>, rotation: Rad<f64>) -> Self {
+        let rotation = (rotation.0.cos(), rotation.0.sin());
+        let size = Vector2::new(hitbox.size.x as f64, hitbox.size.y as f64);
+        let (half, kind) = match hitbox.hitbox_type {
+            HitboxType::Rectangle => (size / 2.0, ShapeKind::Rectangle),
+            HitboxType::Ellipse => (size / 2.0, ShapeKind::Ellipse),
+            HitboxType::Circle => (Vector2::new(size.x, size.x) / 2.0, ShapeKind::Ellipse),
+        };
+        Shape {
+            // the offset turns with the entity
+            center: position + rotate(hitbox.offset, rotation),
+            half,
+            rotation,
+            kind,
+            sweep: Vector2::zero(),
+        }
+    }
+
+    // radius of a circle around the center containing the whole shape
+    fn bounding_radius(&self) -> f64 {
+        let extent = match self.kind {
+            ShapeKind::Rectangle => self.half.magnitude(),
+            ShapeKind::Ellipse => self.half.x.max(self.half.y),
+        };
+        extent + self.sweep.magnitude()
+    }
+
+    // furthest point of the shape in direction d
+    fn support(&self, d: Vector2<f64>) -> Vector2<f64> {
+        let (cos, sin) = self.rotation;
+        let local = rotate(d, (cos, -sin));
+        let point = match self.kind {
+            ShapeKind::Rectangle => {
+                Vector2::new(self.half.x.copysign(local.x), self.half.y.copysign(local.y))
+            }
+            ShapeKind::Ellipse => {
+                // (a²dx, b²dy) / |(a dx, b dy)|
+                let scaled = Vector2::new(self.half.x * local.x, self.half.y * local.y);
+                let length = scaled.magnitude();
+                if length == 0.0 {
+                    Vector2::zero()
+                } else {
+                    Vector2::new(self.half.x * scaled.x, self.half.y * scaled.y) / length
+                }
+            }
+        };
+        let swept = if d.dot(self.sweep) > 0.0 {
+            self.sweep
+        } else {
+            Vector2::zero()
+        };
+        self.center + rotate(point, self.rotation) + swept
+    }
+}
+
+// whether two convex shapes overlap, by gjk on their minkowski difference
+fn intersects(a: &Shape, b: &Shape) -> bool {
+    let support = |d: Vector2<f64>| a.support(d) - b.support(-d);
+    let mut d = b.center - a.center;
+    if d == Vector2::zero() {
+        d = Vector2::unit_x();
+    }
+    let mut simplex = vec![support(d)];
+    d = -simplex[0];
+    // each iteration gets closer to the origin; curved shapes can take a few more
+    for _ in 0..64 {
+        if d == Vector2::zero() {
+            return true;
+        }
+        let point = support(d);
+        if point.dot(d) < 0.0 {
+            return false;
+        }
+        simplex.push(point);
+        if contains_origin(&mut simplex, &mut d) {
+            return true;
+        }
+    }
+    false
+}
+
+// reduce the simplex to the part nearest the origin, pointing d towards the origin from it
+fn contains_origin(simplex: &mut Vec<Vector2<f64>>, d: &mut Vector2<f64>) -> bool {
+    let perpendicular = |v: Vector2<f64>| Vector2::new(-v.y, v.x);
+    let a = *simplex.last().unwrap();
+    let ao = -a;
+    match simplex.len() {
+        2 => {
+            let ab = simplex[0] - a;
+            if ab.dot(ao) > 0.0 {
+                let mut normal = perpendicular(ab);
+                if normal.dot(ao) < 0.0 {
+                    normal = -normal;
+                }
+                *d = normal;
+            } else {
+                *simplex = vec![a];
+                *d = ao;
+            }
+            false
+        }
+        _ => {
+            let (c, b) = (simplex[0], simplex[1]);
+            let (ab, ac) = (b - a, c - a);
+            let mut ab_normal = perpendicular(ab);
+            if ab_normal.dot(ac) > 0.0 {
+                ab_normal = -ab_normal;
+            }
+            let mut ac_normal = perpendicular(ac);
+            if ac_normal.dot(ab) > 0.0 {
+                ac_normal = -ac_normal;
+            }
+            if ab_normal.dot(ao) > 0.0 {
+                *simplex = vec![b, a];
+                *d = ab_normal;
+                false
+            } else if ac_normal.dot(ao) > 0.0 {
+                *simplex = vec![c, a];
+                *d = ac_normal;
+                false
+            } else {
+                true
+            }
+        }
+    }
+}
+
+/// Tests the colliding entities near each target against its hurtbox. Entity hitboxes
+/// turn with the entity's rotation, hurtboxes are axis aligned. `grid` has to have been
+/// rebuilt from `entities` as they are now.
+pub fn detect(
+    entities: &[ExecutionEnvironment],
+    targets: &TargetMap,
+    grid: &SpatialHash,
+    options: &CollisionOptions,
+) -> Vec<Hit> {
+    let mut hits: Vec<Hit> = Vec::new();
+    for (id, target) in targets {
+        let hurtbox = match &target.hurtbox {
+            Some(hurtbox) => Shape::new(hurtbox, target.position, Rad(0.0)),
+            None => continue,
+        };
+        // anything further than this can't reach the hurtbox
+        let reach = hurtbox.bounding_radius() + grid.reach();
+        for i in grid.within_radius(hurtbox.center, reach) {
+            let environment = &entities[i];
+            let entity = &environment.entity;
+            if !entity.collides {
+                continue;
+            }
+            let rotation = Rad((entity.rotation.0 as f64).to_radians());
+            let mut shape = Shape::new(&entity.hitbox, entity.position, rotation);
+            if options.swept {
+                shape.sweep = environment.previous_position - entity.position;
+            }
+            if intersects(&shape, &hurtbox) {
+                hits.push(Hit {
+                    entity: Some(i),
+                    despawned: None,
+                    target: *id,
+                    position: entity.position,
+                });
+            }
+        }
+    }
+    // in entity order, so despawning can walk them back to front
+    hits.sort_by_key(|hit| (hit.entity, hit.target));
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::target::Target;
+    use crate::interpreter::Interpreter;
+    use crate::parser::parser::Parser;
+    use cgmath::Deg;
+
+    fn hitbox(w: u16, h: u16, hitbox_type: HitboxType) -> Hitbox {
+        Hitbox {
+            size: Vector2::new(w, h),
+            offset: Vector2::zero(),
+            hitbox_type,
+        }
+    }
+
+    fn bullet(position: Vector2<f64>, rotation: f32, hitbox: Hitbox) -> ExecutionEnvironment {
+        let mut entity = Entity::new();
+        entity.position = position;
+        entity.rotation = Deg(rotation);
+        entity.hitbox = hitbox;
+        ExecutionEnvironment::new(&entity)
+    }
+
+    // which entities touch a single target with this hurtbox
+    fn hit_by(
+        entities: &[ExecutionEnvironment],
+        position: Vector2<f64>,
+        hurtbox: Hitbox,
+        options: &CollisionOptions,
+    ) -> Vec<Option<usize>> {
+        let mut target = Target::new(position, None);
+        target.hurtbox = Some(hurtbox);
+        let targets = TargetMap::from([(1, target)]);
+        let mut grid = SpatialHash::default();
+        grid.rebuild(entities);
+        detect(entities, &targets, &grid, options)
+            .into_iter()
+            .map(|hit| hit.entity)
+            .collect()
+    }
+
+    #[test]
+    fn rotated_rectangle_against_ellipse() {
+        // a long thin bar, and an ellipse off along its diagonal
+        let bar = hitbox(40, 4, HitboxType::Rectangle);
+        let ellipse = hitbox(10, 6, HitboxType::Ellipse);
+        let at = Vector2::new(12.0, 12.0);
+        let options = CollisionOptions::default();
+        let turned = |rotation| vec![bullet(Vector2::zero(), rotation, bar.clone())];
+        assert_eq!(
+            hit_by(&turned(45.0), at, ellipse.clone(), &options),
+            vec![Some(0)]
+        );
+        assert_eq!(hit_by(&turned(0.0), at, ellipse.clone(), &options), vec![]);
+        assert_eq!(
+            hit_by(&turned(-45.0), at, ellipse.clone(), &options),
+            vec![]
+        );
+        assert_eq!(hit_by(&turned(225.0), at, ellipse, &options), vec![Some(0)]);
+    }
+
+    #[test]
+    fn circles_miss_past_a_rectangle_corner() {
+        let square = vec![bullet(
+            Vector2::zero(),
+            0.0,
+            hitbox(10, 10, HitboxType::Rectangle),
+        )];
+        let circle = hitbox(10, 0, HitboxType::Circle);
+        let options = CollisionOptions::default();
+        // the corner is at (5, 5), sqrt(32) from (9, 9) and sqrt(18) from (8, 8)
+        assert_eq!(
+            hit_by(&square, Vector2::new(9.0, 9.0), circle.clone(), &options),
+            vec![]
+        );
+        assert_eq!(
+            hit_by(&square, Vector2::new(8.0, 8.0), circle, &options),
+            vec![Some(0)]
+        );
+    }
+
+    #[test]
+    fn offsets_turn_with_the_entity() {
+        let mut offset = hitbox(4, 4, HitboxType::Rectangle);
+        offset.offset = Vector2::new(20.0, 0.0);
+        let target = hitbox(4, 4, HitboxType::Rectangle);
+        let options = CollisionOptions::default();
+        let turned = |rotation| vec![bullet(Vector2::zero(), rotation, offset.clone())];
+        assert_eq!(
+            hit_by(
+                &turned(0.0),
+                Vector2::new(20.0, 0.0),
+                target.clone(),
+                &options
+            ),
+            vec![Some(0)]
+        );
+        assert_eq!(
+            hit_by(
+                &turned(90.0),
+                Vector2::new(20.0, 0.0),
+                target.clone(),
+                &options
+            ),
+            vec![]
+        );
+        assert_eq!(
+            hit_by(&turned(90.0), Vector2::new(0.0, 20.0), target, &options),
+            vec![Some(0)]
+        );
+    }
+
+    #[test]
+    fn swept_bullets_cant_skip_a_thin_target() {
+        // from one side of a wall to the other in a single frame
+        let mut fast = bullet(
+            Vector2::new(100.0, 0.0),
+            0.0,
+            hitbox(2, 2, HitboxType::Circle),
+        );
+        fast.previous_position = Vector2::new(-100.0, 0.0);
+        let fast = vec![fast];
+        let wall = hitbox(4, 40, HitboxType::Rectangle);
+        let mut options = CollisionOptions::default();
+        assert_eq!(
+            hit_by(&fast, Vector2::zero(), wall.clone(), &options),
+            vec![]
+        );
+        options.swept = true;
+        assert_eq!(
+            hit_by(&fast, Vector2::zero(), wall.clone(), &options),
+            vec![Some(0)]
+        );
+        // but a wall off to the side of the path still isn't hit
+        assert_eq!(
+            hit_by(&fast, Vector2::new(0.0, 30.0), wall, &options),
+            vec![]
+        );
+    }
+
+    // two still bullets, one of them sitting on a target
+    fn interpreter(despawn_on_hit: bool) -> Interpreter {
+        let head = Parser::parse_from_string(String::new()).unwrap();
+        let mut interpreter = Interpreter::from_parse_result(head).unwrap();
+        interpreter.collision.despawn_on_hit = despawn_on_hit;
+        interpreter.set_target(1, Vector2::new(200.0, 200.0), None);
+        interpreter.set_hurtbox(1, Some(hitbox(10, 10, HitboxType::Circle)));
+        for position in [Vector2::new(200.0, 200.0), Vector2::new(0.0, 0.0)] {
+            let mut entity = Entity::new();
+            entity.position = position;
+            interpreter.spawn_direct(&entity);
+        }
+        interpreter
+    }
+
+    #[test]
+    fn despawning_on_hit() {
+        let mut kept = interpreter(false);
+        kept.step();
+        assert_eq!(kept.entities.len(), 2);
+        assert_eq!(kept.hits.len(), 1);
+        assert_eq!(kept.hits[0].entity, Some(0));
+        assert!(kept.hits[0].despawned.is_none());
+        // and it hits again the next frame, still being there
+        kept.step();
+        assert_eq!(kept.hits.len(), 1);
+
+        let mut despawned = interpreter(true);
+        despawned.step();
+        assert_eq!(despawned.entities.len(), 1);
+        assert_eq!(
+            despawned.entities[0].entity.position,
+            Vector2::new(0.0, 0.0)
+        );
+        assert_eq!(despawned.hits.len(), 1);
+        assert_eq!(despawned.hits[0].entity, None);
+        let hit = despawned.hits[0].despawned.as_ref().map(|e| e.position);
+        assert_eq!(hit, Some(Vector2::new(200.0, 200.0)));
+        despawned.step();
+        assert!(despawned.hits.is_empty());
+    }
+}
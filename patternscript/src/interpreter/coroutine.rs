@@ -0,0 +1,387 @@
+use super::entity::*;
+use super::evaluate::{substitute, Evaluate};
+use super::primitive::*;
+use super::*;
+use cgmath::Deg;
+use std::rc::Rc;
+
+// how many patterns can be nested inside each other with run before giving up
+//   a pattern that runs itself would otherwise never get past its first statement
+pub const MAX_RUN_DEPTH: usize = 16;
+
+// a pattern being run by an entity, resumed once per frame
+//
+// rather than unrolling the whole pattern into timed actions up front, the coroutine keeps a
+// stack of the blocks and for loops it is currently inside of and runs statements until it
+// hits a wait. memory is then proportional to the nesting depth, not the length of the pattern,
+// so patterns can also loop forever.
+#[derive(Debug, Clone)]
+pub struct Coroutine {
+    frames: Vec<Frame>,
+    // the pattern's actions, restarted at the beginning of each cycle
+    actions: Rc<Vec<Node>>,
+    // pattern level definitions each cycle's scope starts from
+    scope: Values,
+    repeat: Repeat,
+    // frames this coroutine has been resumed for
+    clock: u32,
+    resume_at: u32,
+    cycle_started_at: Option<u32>,
+    // patterns this one is being run inside of
+    depth: usize,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Repeat {
+    Cycles(i64),
+    // run new cycles until the clock reaches this frame
+    Until(u32),
+    Forever,
+}
+
+#[derive(Debug, Clone)]
+enum Frame {
+    Block(BlockFrame),
+    For(ForFrame),
+    // a pattern started with run, the rest of the block waits for it to finish
+    Run(Box<Coroutine>),
+}
+
+#[derive(Debug, Clone)]
+struct BlockFrame {
+    statements: Rc<Vec<Node>>,
+    pc: usize,
+    // assignments in this block are visible to the statements after them and nested frames
+    scope: Values,
+}
+
+#[derive(Debug, Clone)]
+struct ForFrame {
+    body: Rc<Vec<Node>>,
+    names: Vec<String>,
+    ranges: Vec<(i64, i64)>,
+    condition: Condition,
+    // next combination of the loop variables, counted over all of them
+    index: i64,
+    total: i64,
+    scope: Values,
+}
+
+impl ForFrame {
+    fn new(fd: &ForData, scope: Values) -> Self {
+        let mut names: Vec<String> = Vec::new();
+        let mut ranges: Vec<(i64, i64)> = Vec::new();
+        for (var, range_expr) in &fd.initial_definitions {
+            if let ExpressionKind::Range(a, b) = range_expr.kind {
+                names.push(var.clone());
+                ranges.push((a, b));
+            }
+        }
+        let total = ranges.iter().map(|(a, b)| (b - a).max(0)).product();
+        ForFrame {
+            body: Rc::new(fd.body.statements.clone()),
+            names,
+            ranges,
+            condition: fd.condition.clone(),
+            index: 0,
+            total,
+            scope,
+        }
+    }
+
+    // scope for the next iteration which passes the loop's condition, if any are left
+    //   (i = 0...3, j = 0...3) counts like a number with i as the most significant digit:
+    //   {i:0, j:0}, {i:0, j:1}, {i:0, j:2}, {i:1, j:0}, ...
+    fn next_scope(&mut self, globals: &Values) -> Option<Values> {
+        while self.index < self.total {
+            let mut remainder = self.index;
+            let mut bindings = self.scope.clone();
+            for (name, (start, end)) in self.names.iter().zip(self.ranges.iter()).rev() {
+                let length = end - start;
+                bindings.insert(
+                    name.clone(),
+                    ExpressionKind::Int(start + remainder % length).into(),
+                );
+                remainder /= length;
+            }
+            self.index += 1;
+            // handle according to conditional inclusion/exclusion rules
+            if let Ok(Primitive::Bool(true)) = self
+                .condition
+                .clone()
+                .eval(&with_globals(&bindings, globals))
+            {
+                return Some(bindings);
+            }
+        }
+        None
+    }
+}
+
+// globals describe the entity on the frame being run, locals shadow them
+fn with_globals(scope: &Values, globals: &Values) -> Values {
+    let mut values = globals.clone();
+    values.extend(scope.iter().map(|(k, v)| (k.clone(), v.clone())));
+    values
+}
+
+// bind a variable for the statements following it in the same block (and blocks nested in those)
+//   the rvalue is evaluated on the frame the assignment runs, so `angle = towards_player` before
+//   a wait keeps aiming where the player was, and x = x + 1 refers to the previous x
+//   values without a literal form (booleans, or names only bound later such as path arguments)
+//   are kept as an expression over the current scope instead
+fn assign(scope: &mut Values, ad: &AssignmentData, globals: &Values) {
+    scope.insert(ad.lvalue.clone(), evaluate_now(&ad.rvalue, scope, globals));
+}
+
+// expr as a literal of its value on this frame, or over the current scope if it has none
+fn evaluate_now(expr: &ExpressionType, scope: &Values, globals: &Values) -> ExpressionType {
+    let expr = substitute(expr, scope);
+    match expr.clone().eval(globals) {
+        Ok(primitive) => primitive.into_expression(expr.span).unwrap_or(expr),
+        Err(_) => expr,
+    }
+}
+
+// the pattern a run or fire statement names with its parameters bound to the arguments, as they
+// are on the frame it runs; None if there's no such pattern or it takes a different number
+fn pattern_call<'p>(
+    cd: &PatternCallData,
+    patterns: &'p PatternMap,
+    scope: &Values,
+    globals: &Values,
+) -> Option<(&'p PatternData, Values)> {
+    let pd = patterns.get(&cd.pattern)?;
+    match &pd.arguments.kind {
+        ExpressionKind::Vector(parameters) if parameters.len() == cd.arguments.len() => {}
+        _ => return None,
+    }
+    let arguments: Vec<ExpressionType> = cd
+        .arguments
+        .iter()
+        .map(|arg| evaluate_now(arg, scope, globals))
+        .collect();
+    Some((pd, Entity::align_function_args(&pd.arguments, &arguments)))
+}
+
+// parser precondition that waitdata::variants are of specific types
+//   frames: int
+//   time:   int/float
+fn wait_frames(wd: &WaitData, scope: &Values, fps: u16) -> u32 {
+    match wd {
+        WaitData::Frames(f) => match f.clone().eval(scope) {
+            Ok(Primitive::I64(i)) => i.max(0) as u32,
+            _ => 0,
+        },
+        WaitData::Time(t) => match t.clone().eval(scope) {
+            // wait negative seconds doesn't make sense
+            Ok(Primitive::I64(i)) => i.max(0) as u32 * fps as u32,
+            Ok(Primitive::F64(f)) => (f.max(0.0) * fps as f64).floor() as u32,
+            _ => 0,
+        },
+    }
+}
+
+impl Coroutine {
+    pub fn new(pd: &PatternData, fps: u16) -> Self {
+        Coroutine::child(pd, Values::new(), fps)
+    }
+
+    // a pattern started by another, seeing its own definitions with its arguments over them
+    //   nothing of the pattern that started it, which is what analysis checks it against
+    pub fn child(pd: &PatternData, arguments: Values, fps: u16) -> Self {
+        let mut scope = pd.definitions.clone();
+        scope.extend(arguments);
+        let actions = match scope.get("actions").map(|a| &a.kind) {
+            Some(ExpressionKind::Block(block)) => block.statements.clone(),
+            _ => Vec::new(),
+        };
+        // dispatch on iteration type for number of cycles through the behavior
+        let repeat = match scope.get("iteration_type").map(|t| &t.kind) {
+            Some(ExpressionKind::Variable(t)) if t == "time" => match scope.get("length") {
+                Some(ExpressionType {
+                    kind: ExpressionKind::Duration(wd),
+                    ..
+                }) => Repeat::Until(wait_frames(wd, &scope, fps)),
+                _ => Repeat::Until(0),
+            },
+            Some(ExpressionKind::Variable(t)) if t == "cycles" => match scope.get("length") {
+                Some(ExpressionType {
+                    kind: ExpressionKind::Int(i),
+                    ..
+                }) => Repeat::Cycles(*i),
+                _ => Repeat::Cycles(1),
+            },
+            Some(ExpressionKind::Variable(t)) if t == "loop" => Repeat::Forever,
+            _ => Repeat::Cycles(1),
+        };
+        Coroutine {
+            frames: Vec::new(),
+            actions: Rc::new(actions),
+            scope,
+            repeat,
+            clock: 0,
+            resume_at: 0,
+            cycle_started_at: None,
+            depth: 0,
+            finished: false,
+        }
+    }
+
+    fn start_cycle(&mut self) -> bool {
+        let start = match &mut self.repeat {
+            Repeat::Cycles(n) => {
+                *n -= 1;
+                *n >= 0
+            }
+            Repeat::Until(end) => self.clock < *end,
+            Repeat::Forever => true,
+        };
+        if start {
+            self.cycle_started_at = Some(self.clock);
+            self.frames.push(Frame::Block(BlockFrame {
+                statements: Rc::clone(&self.actions),
+                pc: 0,
+                scope: self.scope.clone(),
+            }));
+        }
+        start
+    }
+
+    /// Runs the pattern from where it left off until its next wait, returning the entities
+    /// spawned along the way. Called once per frame with the globals of the running entity
+    /// as of that frame, so everything fired sees where the entity is now.
+    pub fn resume(
+        &mut self,
+        patterns: &PatternMap,
+        paths: &PathMap,
+        bullets: &BulletMap,
+        globals: &Values,
+        fps: u16,
+    ) -> Vec<Entity> {
+        let mut spawned: Vec<Entity> = Vec::new();
+        while !self.finished && self.clock >= self.resume_at {
+            let frame = match self.frames.last_mut() {
+                Some(frame) => frame,
+                None => {
+                    // a timed or endless pattern without any waits would never yield
+                    if self.cycle_started_at == Some(self.clock)
+                        && !matches!(self.repeat, Repeat::Cycles(_))
+                    {
+                        self.resume_at = self.clock + 1;
+                        break;
+                    }
+                    self.finished = !self.start_cycle();
+                    continue;
+                }
+            };
+            let pushed = match frame {
+                Frame::Run(child) => {
+                    spawned.extend(child.resume(patterns, paths, bullets, globals, fps));
+                    if !child.finished {
+                        break;
+                    }
+                    self.frames.pop();
+                    None
+                }
+                Frame::For(ff) => match ff.next_scope(globals) {
+                    Some(scope) => Some(Frame::Block(BlockFrame {
+                        statements: Rc::clone(&ff.body),
+                        pc: 0,
+                        scope,
+                    })),
+                    None => {
+                        self.frames.pop();
+                        None
+                    }
+                },
+                Frame::Block(bf) => {
+                    if bf.pc >= bf.statements.len() {
+                        self.frames.pop();
+                        continue;
+                    }
+                    let statements = Rc::clone(&bf.statements);
+                    let statement = &statements[bf.pc];
+                    bf.pc += 1;
+                    match statement {
+                        Node::Assign(ad) => {
+                            assign(&mut bf.scope, ad, globals);
+                            None
+                        }
+                        Node::Spawn(sd) => {
+                            spawned.push(Entity::from_values(
+                                &sd.definitions,
+                                patterns,
+                                paths,
+                                bullets,
+                                globals.clone(),
+                                Some(bf.scope.clone()),
+                            ));
+                            None
+                        }
+                        Node::Wait(wd) => {
+                            let frames = wait_frames(wd, &with_globals(&bf.scope, globals), fps);
+                            if frames > 0 {
+                                self.resume_at = self.clock + frames;
+                            }
+                            None
+                        }
+                        Node::For(fd) => Some(Frame::For(ForFrame::new(fd, bf.scope.clone()))),
+                        Node::Run(cd) if self.depth < MAX_RUN_DEPTH => {
+                            pattern_call(cd, patterns, &bf.scope, globals).map(|(pd, arguments)| {
+                                let mut child = Coroutine::child(pd, arguments, fps);
+                                child.depth = self.depth + 1;
+                                Frame::Run(Box::new(child))
+                            })
+                        }
+                        // runs nested deeper than this are reported by analysis
+                        Node::Run(_) => None,
+                        Node::Fire(cd) => {
+                            if let Some((_, arguments)) =
+                                pattern_call(cd, patterns, &bf.scope, globals)
+                            {
+                                // the emitter carries on the way the firing entity was going
+                                let vector = |name: &str| match globals
+                                    .get(name)
+                                    .map(|p| p.clone().eval(globals))
+                                {
+                                    Some(Ok(Primitive::FloatVec(f))) => Vector2::new(f[0], f[1]),
+                                    _ => Vector2::new(0.0, 0.0),
+                                };
+                                let rotation = match globals
+                                    .get("entity_rotation")
+                                    .map(|r| r.clone().eval(globals))
+                                {
+                                    Some(Ok(Primitive::F64(f))) => Deg(f as f32),
+                                    _ => Deg(0.0),
+                                };
+                                spawned.push(Entity::emitter(
+                                    &cd.pattern,
+                                    arguments,
+                                    vector("entity_position"),
+                                    rotation,
+                                    vector("entity_velocity"),
+                                ));
+                            }
+                            None
+                        }
+                        // only top level, should already be parsed into the reference maps
+                        Node::Head(_)
+                        | Node::Pattern(_)
+                        | Node::Bullet(_)
+                        | Node::Path(_)
+                        | Node::Function(_) => None,
+                        // only meaningful inside a function, reported by analysis
+                        Node::Return(_) => None,
+                    }
+                }
+            };
+            if let Some(frame) = pushed {
+                self.frames.push(frame);
+            }
+        }
+        self.clock += 1;
+        spawned
+    }
+}
@@ -0,0 +1,88 @@
+use std::f64::consts::PI;
+
+// how far along u is for an easing named ease_<in, out or in_out>_<curve>, None if name
+// isn't one; in starts slow, out ends slow and in_out does both
+//   u outside [0, 1] is clamped, so easings can be fed a raw t / duration
+pub fn ease(name: &str, u: f64) -> Option<f64> {
+    let (direction, curve) = name
+        .strip_prefix("ease_in_out_")
+        .map(|curve| (Direction::InOut, curve))
+        .or_else(|| name.strip_prefix("ease_in_").map(|c| (Direction::In, c)))
+        .or_else(|| name.strip_prefix("ease_out_").map(|c| (Direction::Out, c)))?;
+    let ease_in: fn(f64) -> f64 = match curve {
+        "quad" => |u| u * u,
+        "cubic" => |u| u * u * u,
+        "sine" => |u| 1.0 - (u * PI / 2.0).cos(),
+        "expo" => |u| {
+            if u == 0.0 {
+                0.0
+            } else {
+                2f64.powf(10.0 * u - 10.0)
+            }
+        },
+        "back" => |u| {
+            const OVERSHOOT: f64 = 1.70158;
+            u * u * ((OVERSHOOT + 1.0) * u - OVERSHOOT)
+        },
+        "elastic" => |u| {
+            if u == 0.0 || u == 1.0 {
+                u
+            } else {
+                -(2f64.powf(10.0 * u - 10.0)) * ((u * 10.0 - 10.75) * 2.0 * PI / 3.0).sin()
+            }
+        },
+        _ => return None,
+    };
+    let u = u.clamp(0.0, 1.0);
+    // out is in played backwards, in_out is in for the first half and out for the second
+    Some(match direction {
+        Direction::In => ease_in(u),
+        Direction::Out => 1.0 - ease_in(1.0 - u),
+        Direction::InOut if u < 0.5 => ease_in(2.0 * u) / 2.0,
+        Direction::InOut => 1.0 - ease_in(2.0 - 2.0 * u) / 2.0,
+    })
+}
+
+enum Direction {
+    In,
+    Out,
+    InOut,
+}
+
+// cubic bezier through p0 and p3, pulled towards p1 and p2, each point a number or a vector
+pub fn bezier(points: [&[f64]; 4], u: f64) -> Vec<f64> {
+    let v = 1.0 - u;
+    let weights = [v * v * v, 3.0 * v * v * u, 3.0 * v * u * u, u * u * u];
+    (0..points[0].len())
+        .map(|i| (0..4).map(|p| weights[p] * points[p][i]).sum())
+        .collect()
+}
+
+// uniform catmull-rom through every point, u from 0 at the first to 1 at the last
+//   the end points are repeated so the curve reaches them
+pub fn catmull_rom(points: &[Vec<f64>], u: f64) -> Vec<f64> {
+    let segments = points.len() - 1;
+    if segments == 0 {
+        return points[0].clone();
+    }
+    let along = u.clamp(0.0, 1.0) * segments as f64;
+    let segment = (along.floor() as usize).min(segments - 1);
+    let s = along - segment as f64;
+    let point = |i: isize| &points[i.clamp(0, segments as isize) as usize];
+    let i = segment as isize;
+    let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+    (0..p1.len())
+        .map(|e| {
+            let (a, b, c, d) = (p0[e], p1[e], p2[e], p3[e]);
+            0.5 * (2.0 * b
+                + (c - a) * s
+                + (2.0 * a - 5.0 * b + 4.0 * c - d) * s * s
+                + (3.0 * b - a - 3.0 * c + d) * s * s * s)
+        })
+        .collect()
+}
+
+// 0 up to length and back down again, every 2 * length
+pub fn ping_pong(t: f64, length: f64) -> f64 {
+    length - (t.rem_euclid(2.0 * length) - length).abs()
+}
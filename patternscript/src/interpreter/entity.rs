@@ -1,23 +1,44 @@
 use std::collections::HashMap;
 
-use super::callback::{Callback, TimedCallback};
-use super::evaluate::Evaluate;
+use super::bytecode::{self, PathProgram};
+use super::coroutine::Coroutine;
+use super::evaluate::{is_pure, substitute, Evaluate};
+use super::motion::{Acceleration, Motion};
 use super::primitive::Primitive;
-use super::{BulletMap, EntityMap, PathMap, PatternMap};
-use crate::parser::parser::{ArithmeticExpression, ExpressionType, Node, UnaryOperator, Values};
-use cgmath::{Angle, Deg, Vector2, Vector3};
+use super::{lineage, BulletMap, PathMap, PatternMap};
+use crate::parser::parser::{
+    ArithmeticExpression, ExpressionKind, ExpressionType, PathData, Values,
+};
+use cgmath::{Angle, Deg, InnerSpace, Vector2, Vector4};
 
-#[derive(Clone, Debug)]
-pub enum VelocityType {
-    Simple(Vector2<f64>),
-    Acceleration(Vector2<f64>),
-    Path(ExpressionType),
-}
+// fields read as they're written rather than evaluated on spawn: the bullet type, calls to
+// paths and patterns, and the speed curve, which is evaluated every frame
+pub const DEFERRED_FIELDS: [&str; 5] = [
+    "type",
+    "position_fn",
+    "velocity_fn",
+    "behavior",
+    "speed_over_time",
+];
 
 #[derive(Clone, Debug)]
 pub enum HitboxType {
     Rectangle,
     Ellipse,
+    // size.x across
+    Circle,
+}
+
+impl HitboxType {
+    // the shape named in a bullet or spawn block, `shape = "circle";`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rectangle" => Some(HitboxType::Rectangle),
+            "ellipse" => Some(HitboxType::Ellipse),
+            "circle" => Some(HitboxType::Circle),
+            _ => None,
+        }
+    }
 }
 #[derive(Clone, Debug)]
 pub struct Hitbox {
@@ -26,9 +47,63 @@ pub struct Hitbox {
     pub hitbox_type: HitboxType,
 }
 
+// what happens to an entity that leaves the interpreter's playfield
+//   margins are how far past the edge it can go first, so it's fully off screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoundsPolicy {
+    Despawn { margin: f64 },
+    Wrap { margin: f64 },
+    Bounce,
+    Unbounded,
+}
+
+impl BoundsPolicy {
+    pub const DEFAULT_MARGIN: f64 = 16.0;
+
+    // the policy named in a bullet or spawn block, `bounds = "wrap";`
+    pub fn from_name(name: &str, margin: f64) -> Option<Self> {
+        match name {
+            "despawn" => Some(BoundsPolicy::Despawn { margin }),
+            "wrap" => Some(BoundsPolicy::Wrap { margin }),
+            "bounce" => Some(BoundsPolicy::Bounce),
+            "none" => Some(BoundsPolicy::Unbounded),
+            _ => None,
+        }
+    }
+}
+
+// how a renderer should combine an entity with what's already drawn under it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    Alpha,
+    Add,
+    Subtract,
+    Multiply,
+}
+
+impl BlendMode {
+    // the mode named in a bullet or spawn block, `blend = "add";`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "alpha" => Some(BlendMode::Alpha),
+            "add" => Some(BlendMode::Add),
+            "subtract" => Some(BlendMode::Subtract),
+            "multiply" => Some(BlendMode::Multiply),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Behavior {
     Pattern(String),
+    // a pattern started by `fire` or a `behavior` field, with its parameters already bound
+    //   an emitter only exists to run it and is removed once it finishes
+    Child {
+        pattern: String,
+        arguments: Values,
+        emitter: bool,
+    },
     Simple,
 }
 #[derive(Clone, Debug)]
@@ -45,10 +120,21 @@ pub struct Entity {
     // x and y are given as things that can be evaled by their own scope
     pub position_fn: Option<Values>,
     pub velocity_fn: Option<Values>,
+    pub motion: Motion,
 
-    pub color: Vector3<u8>,
+    // how it's drawn, none of which changes what it collides with
+    //   sprite is a name for the host to look up, color is rgba and scale multiplies the sprite
+    //   higher z_order is drawn on top
+    pub sprite: Option<String>,
+    pub color: Vector4<u8>,
+    pub scale: f64,
+    pub z_order: i32,
+    pub blend: BlendMode,
     pub hitbox: Hitbox,
     pub behavior: Behavior,
+    // tested against target hurtboxes, off for emitters that shouldn't hurt the player
+    pub collides: bool,
+    pub bounds: BoundsPolicy,
 
     pub instance_vars: Option<Values>,
 }
@@ -59,6 +145,14 @@ pub struct ExecutionEnvironment {
     pub duration: u32,
     pub current_wait: u32,
     pub entity: Entity,
+    // where the entity was before the last move, for swept collisions
+    pub previous_position: Vector2<f64>,
+    // position_fn/velocity_fn compiled once on spawn, None when they don't compile
+    //   (those are left to the tree walker, which reports the error when evaluated)
+    pub position_program: Option<PathProgram>,
+    pub velocity_program: Option<PathProgram>,
+    // 1 or -1 on each axis, mirroring a velocity_fn's output once it has bounced off an edge
+    pub reflection: Vector2<f64>,
 }
 
 impl ExecutionEnvironment {
@@ -68,11 +162,21 @@ impl ExecutionEnvironment {
             duration: e.lifetime,
             current_wait: 0,
             entity: e.clone(),
+            previous_position: e.position,
+            position_program: e
+                .position_fn
+                .as_ref()
+                .and_then(|f| PathProgram::compile(&e.path_environment(f)).ok()),
+            velocity_program: e
+                .velocity_fn
+                .as_ref()
+                .and_then(|f| PathProgram::compile(&e.path_environment(f)).ok()),
+            reflection: Vector2::new(1.0, 1.0),
         }
     }
 }
 
-impl<'a> Entity {
+impl Entity {
     pub fn new() -> Self {
         Entity {
             position: Vector2 { x: 0.0, y: 0.0 },
@@ -80,80 +184,363 @@ impl<'a> Entity {
             rotation: Deg(0.),
             speed: None,
             lifetime: 600,
-            color: Vector3 { x: 255, y: 0, z: 0 },
+            sprite: None,
+            color: Vector4 {
+                x: 255,
+                y: 0,
+                z: 0,
+                w: 255,
+            },
+            scale: 1.0,
+            z_order: 0,
+            blend: BlendMode::Alpha,
             hitbox: Hitbox {
                 size: Vector2 { x: 3, y: 3 },
                 offset: Vector2 { x: 0.0, y: 0.0 },
                 hitbox_type: HitboxType::Rectangle,
             },
             behavior: Behavior::Simple,
+            collides: true,
+            bounds: BoundsPolicy::Despawn {
+                margin: BoundsPolicy::DEFAULT_MARGIN,
+            },
             position_fn: None,
             velocity_fn: None,
+            motion: Motion::default(),
             instance_vars: None,
         }
     }
 
-    pub fn compile_behavior(
-        &self,
-        paths: &PathMap,
-        patterns: &PatternMap,
-        ents: &EntityMap,
-        bullets: &BulletMap,
-        globals: Values,
-        fps: u16,
-    ) -> Option<Vec<TimedCallback<'a>>> {
+    pub fn compile_behavior(&self, patterns: &PatternMap, fps: u16) -> Option<Coroutine> {
         match &self.behavior {
-            Behavior::Pattern(pd) => Some(
-                Node::Pattern(patterns.get(pd)?.clone())
-                    .create(paths, patterns, ents, bullets, &globals, fps),
-            ),
+            Behavior::Pattern(pd) => Some(Coroutine::new(patterns.get(pd)?, fps)),
+            Behavior::Child {
+                pattern, arguments, ..
+            } => Some(Coroutine::child(
+                patterns.get(pattern)?,
+                arguments.clone(),
+                fps,
+            )),
             Behavior::Simple => None,
         }
     }
 
-    pub fn extract_color(expression: &ExpressionType, values: &Values) -> Vector3<u8> {
-        if let ExpressionType::Vector(ve) = expression {
-            let color: Vec<u8> = ve
+    // an entity that only runs pattern, harmless and gone once the pattern ends
+    //   starts where the entity firing it is, heading and moving the same way
+    pub fn emitter(
+        pattern: &str,
+        arguments: Values,
+        position: Vector2<f64>,
+        rotation: Deg<f32>,
+        velocity: Vector2<f64>,
+    ) -> Self {
+        let mut entity = Entity::new();
+        entity.position = position;
+        entity.rotation = rotation;
+        entity.velocity = velocity;
+        entity.lifetime = u32::MAX;
+        entity.behavior = Behavior::Child {
+            pattern: pattern.to_string(),
+            arguments,
+            emitter: true,
+        };
+        entity.collides = false;
+        entity.bounds = BoundsPolicy::Unbounded;
+        entity
+    }
+
+    // speed and heading after dt more seconds of its motion, at elapsed frames
+    //   an entity moving by velocity alone keeps its rotation, and heads the way it's going
+    pub fn integrate_motion(&mut self, elapsed: u32, dt: f64) {
+        if self.motion.is_inert() {
+            return;
+        }
+        match self.speed {
+            Some(speed) => {
+                let (speed, heading) = self.motion.step(speed, self.rotation.0 as f64, elapsed, dt);
+                self.speed = Some(speed);
+                self.rotation = Deg(heading as f32).normalize();
+            }
+            None => {
+                let speed = self.velocity.magnitude();
+                let heading = if speed > 0.0 {
+                    self.velocity.y.atan2(self.velocity.x).to_degrees()
+                } else {
+                    self.rotation.0 as f64
+                };
+                let (speed, heading) = self.motion.step(speed, heading, elapsed, dt);
+                let (sin, cos) = heading.to_radians().sin_cos();
+                self.velocity = Vector2::new(speed * cos, speed * sin);
+            }
+        }
+    }
+
+    pub fn is_emitter(&self) -> bool {
+        matches!(self.behavior, Behavior::Child { emitter: true, .. })
+    }
+
+    // everything a path function of this entity can refer to
+    //   its arguments and definitions shadow the spawning scope
+    pub fn path_environment(&self, path_vals: &Values) -> Values {
+        let mut env = self.instance_vars.clone().unwrap_or_default();
+        env.extend(path_vals.iter().map(|(k, v)| (k.clone(), v.clone())));
+        env
+    }
+
+    // (r, g, b) or (r, g, b, a), each an int out of 255 or a float out of 1
+    //   opaque red if it's neither
+    pub fn extract_color(expression: &ExpressionType, values: &Values) -> Vector4<u8> {
+        let component = |p: &Primitive| match p {
+            Primitive::I64(i) => (*i).clamp(0, 255) as u8,
+            Primitive::F64(f) => (f * 255.).clamp(0., 255.) as u8,
+            _ => 0,
+        };
+        let color: Vec<u8> = match &expression.kind {
+            // a literal is read component by component, so (255, 0.5, 0) mixes the two
+            ExpressionKind::Vector(ve) => ve
                 .iter()
-                .map(|e| {
-                    if let Ok(p) = e.clone().eval(&values) {
-                        match p {
-                            Primitive::I64(i) => i as u8,
-                            Primitive::F64(f) => (f * 255.) as u8,
-                            _ => 0,
-                        }
-                    } else {
-                        0
-                    }
-                })
-                .collect();
-            return Vector3::new(color[0], color[1], color[2]);
-        } else {
-            Vector3::new(255, 0, 0)
+                .map(|e| e.clone().eval(values).map_or(0, |p| component(&p)))
+                .collect(),
+            _ => match expression.clone().eval(values) {
+                Ok(Primitive::IntVec(v)) => v
+                    .into_iter()
+                    .map(|i| component(&Primitive::I64(i)))
+                    .collect(),
+                Ok(Primitive::FloatVec(v)) => v
+                    .into_iter()
+                    .map(|f| component(&Primitive::F64(f)))
+                    .collect(),
+                _ => Vec::new(),
+            },
+        };
+        match color[..] {
+            [r, g, b] => Vector4::new(r, g, b, 255),
+            [r, g, b, a] => Vector4::new(r, g, b, a),
+            _ => Vector4::new(255, 0, 0, 255),
+        }
+    }
+
+    // a number field, ints and floats alike
+    fn extract_number(expression: &ExpressionType, values: &Values) -> Option<f64> {
+        match expression.clone().eval(values) {
+            Ok(Primitive::I64(i)) => Some(i as f64),
+            Ok(Primitive::F64(f)) => Some(f),
+            _ => None,
+        }
+    }
+
+    // a two component vector field such as a hitbox size
+    fn extract_pair(expression: &ExpressionType, values: &Values) -> Option<Vector2<f64>> {
+        match expression.clone().eval(values) {
+            Ok(Primitive::FloatVec(f)) if f.len() == 2 => Some(Vector2::new(f[0], f[1])),
+            Ok(Primitive::IntVec(i)) if i.len() == 2 => {
+                Some(Vector2::new(i[0] as f64, i[1] as f64))
+            }
+            _ => None,
+        }
+    }
+
+    // `(w, h)`, or `((w, h), (x, y))` with the hitbox's offset from the entity
+    fn extract_hitbox(
+        expression: &ExpressionType,
+        values: &Values,
+    ) -> Option<(Vector2<f64>, Option<Vector2<f64>>)> {
+        if let ExpressionKind::Vector(parts) = &expression.kind {
+            if let [size, offset] = parts.as_slice() {
+                if let (Some(size), Some(offset)) = (
+                    Entity::extract_pair(size, values),
+                    Entity::extract_pair(offset, values),
+                ) {
+                    return Some((size, Some(offset)));
+                }
+            }
         }
+        Entity::extract_pair(expression, values).map(|size| (size, None))
     }
 
-    pub fn align_function_args(arg_list: &ExpressionType, arg_vals: &ExpressionType) -> Values {
+    // a string field naming one of a set of options
+    fn extract_name(expression: &ExpressionType, values: &Values) -> Option<String> {
+        match expression.clone().eval(values) {
+            Ok(Primitive::String(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn align_function_args(arg_list: &ExpressionType, arg_vals: &[ExpressionType]) -> Values {
         // precondition:
-        //   arg_list and arg_vals are
+        //   arg_list is a vector of variables, as parsed for paths and functions
         //   arg_list.len() == arg_vals.len()
         // create values from list of arguments and their respective values
         // note:
         //   for paths this will make an infinitely recursive eval on the definitions: t -> t, this is
         //   replaced by the time at runtime
         let mut vals: Values = HashMap::new();
-        if let ExpressionType::Vector(arg_list) = arg_list {
-            if let ExpressionType::Vector(arg_vals) = arg_vals {
-                for i in 0..arg_list.len() {
-                    if let ExpressionType::Variable(lhs) = &arg_list[i] {
-                        vals.insert(lhs.clone(), arg_vals[i].clone());
-                    }
+        if let ExpressionKind::Vector(arg_list) = &arg_list.kind {
+            for (parameter, value) in arg_list.iter().zip(arg_vals) {
+                if let ExpressionKind::Variable(lhs) = &parameter.kind {
+                    vals.insert(lhs.clone(), value.clone());
                 }
             }
         }
         vals
     }
 
+    // arguments a path call was given, if it names a path and gives it the right number
+    fn path_call<'p>(
+        call: Option<&'p ExpressionType>,
+        paths: &'p PathMap,
+    ) -> Option<(&'p PathData, &'p [ExpressionType])> {
+        match call {
+            Some(ExpressionType {
+                kind: ExpressionKind::Expr(ArithmeticExpression::Call(path_fn_name, arguments)),
+                ..
+            }) => {
+                let path = paths.get(path_fn_name)?;
+                match &path.arguments.kind {
+                    ExpressionKind::Vector(parameters) if parameters.len() == arguments.len() => {
+                        Some((path, arguments))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // fields of a bullet along with everything it extends, each bullet overriding its parent's
+    fn prefab(name: &str, bullets: &BulletMap) -> Option<Values> {
+        let lineage = lineage(name, |n| bullets.get(n));
+        if lineage.is_empty() {
+            return None;
+        }
+        let mut definitions: Values = HashMap::new();
+        for bd in lineage.iter().rev() {
+            definitions.extend(bd.definitions.clone());
+        }
+        Some(definitions)
+    }
+
+    // the pattern a `behavior = name(args);` field has the entity run, with its parameters
+    // bound to the arguments as they are on spawn
+    //   None if there's no such pattern or it takes a different number of arguments
+    fn behavior_call(
+        expr: &ExpressionType,
+        patterns: &PatternMap,
+        values: &Values,
+    ) -> Option<Behavior> {
+        let (pattern, arguments): (&String, &[ExpressionType]) = match &expr.kind {
+            ExpressionKind::Variable(pattern) => (pattern, &[]),
+            ExpressionKind::Expr(ArithmeticExpression::Call(pattern, arguments)) => {
+                (pattern, arguments)
+            }
+            _ => return None,
+        };
+        let pd = patterns.get(pattern)?;
+        match &pd.arguments.kind {
+            ExpressionKind::Vector(parameters) if parameters.len() == arguments.len() => {}
+            _ => return None,
+        }
+        let arguments: Vec<ExpressionType> = arguments
+            .iter()
+            .map(|argument| {
+                let argument = substitute(argument, values);
+                let span = argument.span;
+                argument
+                    .clone()
+                    .eval(values)
+                    .ok()
+                    .and_then(|p| p.into_expression(span))
+                    .unwrap_or(argument)
+            })
+            .collect();
+        Some(Behavior::Child {
+            pattern: pattern.clone(),
+            arguments: Entity::align_function_args(&pd.arguments, &arguments),
+            emitter: false,
+        })
+    }
+
+    // path arguments are kept as expressions and evaluated every frame, so a random one
+    // would be redrawn each time; draw those once, on spawn
+    fn draw_random_arguments(arguments: &mut Values, values: &Values) {
+        for argument in arguments.values_mut() {
+            if is_pure(argument) {
+                continue;
+            }
+            let span = argument.span;
+            if let Some(drawn) = argument
+                .clone()
+                .eval(values)
+                .ok()
+                .and_then(|p| p.into_expression(span))
+            {
+                *argument = drawn;
+            }
+        }
+    }
+
+    // the fields of a spawn evaluated down to literals over the scope it happens in
+    //   a field sees the other fields but never itself, so `speed = speed * 2;` doubles the
+    //   speed in scope; fields that can't be evaluated, like ones depending on each other in
+    //   a cycle, are left out and keep their defaults
+    fn resolve_fields(fields: &Values, scope: &Values) -> Values {
+        let mut resolved = scope.clone();
+        for name in fields.keys() {
+            resolved.remove(name);
+        }
+        let mut pending: Vec<(&String, &ExpressionType)> = fields
+            .iter()
+            .filter(|(name, _)| !DEFERRED_FIELDS.contains(&name.as_str()))
+            .collect();
+        // in the order they're written, so random fields draw the same numbers every run
+        pending.sort_by_key(|(name, expr)| (expr.span.start, name.as_str()));
+        loop {
+            let remaining = pending.len();
+            pending.retain(|(name, expr)| {
+                // the field's own name is whatever it shadows, if anything
+                if let Some(outer) = scope.get(*name) {
+                    resolved.insert(name.to_string(), outer.clone());
+                }
+                let literal = Entity::literal(expr, &resolved);
+                resolved.remove(*name);
+                match literal {
+                    Some(literal) => {
+                        resolved.insert(name.to_string(), literal);
+                        false
+                    }
+                    None => true,
+                }
+            });
+            if pending.len() == remaining {
+                break;
+            }
+        }
+        for name in DEFERRED_FIELDS {
+            if let Some(expr) = fields.get(name) {
+                resolved.insert(name.to_string(), expr.clone());
+            }
+        }
+        resolved
+    }
+
+    // vectors are kept element by element, so (255, 0.5, 0) stays a mix of ints and floats
+    // and vectors of vectors keep their shape
+    fn literal(expr: &ExpressionType, values: &Values) -> Option<ExpressionType> {
+        match &expr.kind {
+            ExpressionKind::Vector(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| Entity::literal(element, values))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(ExpressionType::new(
+                    ExpressionKind::Vector(elements),
+                    expr.span,
+                ))
+            }
+            _ => expr.clone().eval(values).ok()?.into_expression(expr.span),
+        }
+    }
+
     /// Constructs a new `Entity`, overriding the defaults.
     ///
     /// # Examples
@@ -167,6 +554,11 @@ impl<'a> Entity {
     ///      lifetime = 400;
     /// }
     ///
+    /// bullet big_red : mid_sized = {
+    ///      color = (255, 0, 0);
+    ///      hitbox = (8, 8);
+    /// }
+    ///
     /// ...
     ///
     /// (within a block)
@@ -176,14 +568,21 @@ impl<'a> Entity {
     ///     rotation = angle;
     ///     speed = 200;
     ///     lifetime = 800;
+    ///     behavior = split(3);
     /// }
     /// ```
     ///
     /// will construct an `Entity` first from the default base, then replace the values
     /// with that of the mid_sized bullet definition. After that, the newest definitions
     /// within the spawning block will be used -- that is, the end lifetime will be 800.
+    /// The bullet then runs the `split` pattern with 3 as its argument, firing from
+    /// wherever it has moved to.
+    ///
+    /// A `type = big_red;` spawn is built the same way from mid_sized's definitions with
+    /// big_red's laid over them.
     pub fn from_values(
         values: &Values,
+        patterns: &PatternMap,
         paths: &PathMap,
         bullets: &BulletMap,
         globals: Values,
@@ -191,31 +590,21 @@ impl<'a> Entity {
     ) -> Self {
         let mut entity = Entity::new();
 
-        let mut values = values.clone();
-        let original_vals = values.clone();
-        entity.instance_vars = instance_vals.clone();
-        if let Some(ref mut iv) = entity.instance_vars {
-            iv.extend(globals.clone());
-            values.extend(iv.clone());
-        }
-
-        // bullet prefab data
-        if let Some(ExpressionType::Variable(e_type)) = values.get("type") {
-            if let Some(prefab) = bullets.get(e_type) {
-                for (k, v) in &prefab.definitions {
-                    // TODO: SPRITE, HITBOX, SHAPE
-                    match k.as_str() {
-                        "color" => {
-                            entity.color = Entity::extract_color(v, &values);
-                        }
-                        _ => {}
-                    }
-                }
-                values.extend(prefab.definitions.clone());
-                // re-extend originals for overrides
-                values.extend(original_vals);
-            }
-        }
+        // locals shadow globals, as they do for the rest of the block
+        let mut scope = globals;
+        scope.extend(instance_vals.unwrap_or_default());
+        entity.instance_vars = Some(scope.clone());
+
+        // bullet prefab data, overridden by the spawn's own fields
+        let mut fields = match values.get("type") {
+            Some(ExpressionType {
+                kind: ExpressionKind::Variable(e_type),
+                ..
+            }) => Entity::prefab(e_type, bullets).unwrap_or_default(),
+            _ => HashMap::new(),
+        };
+        fields.extend(values.iter().map(|(k, v)| (k.clone(), v.clone())));
+        let values = Entity::resolve_fields(&fields, &scope);
 
         // spawn data
 
@@ -226,27 +615,17 @@ impl<'a> Entity {
                 _ => 600,
             }
         }
-        if let Some(ExpressionType::Expr(ArithmeticExpression::Unary(
-            UnaryOperator::FunctionCall(path_fn_name),
-            arguments,
-        ))) = values.get("position_fn")
-        {
-            if let Some(path) = paths.get(path_fn_name) {
-                let mut path_vals = Entity::align_function_args(&path.arguments.clone(), arguments);
-                path_vals.extend(path.definitions.clone());
-                entity.position_fn = Some(path_vals);
-            }
+        if let Some((path, arguments)) = Entity::path_call(values.get("position_fn"), paths) {
+            let mut path_vals = Entity::align_function_args(&path.arguments, arguments);
+            Entity::draw_random_arguments(&mut path_vals, &values);
+            path_vals.extend(path.definitions.clone());
+            entity.position_fn = Some(path_vals);
         }
-        if let Some(ExpressionType::Expr(ArithmeticExpression::Unary(
-            UnaryOperator::FunctionCall(path_fn_name),
-            arguments,
-        ))) = values.get("velocity_fn")
-        {
-            if let Some(path) = paths.get(path_fn_name) {
-                let mut path_vals = Entity::align_function_args(&path.arguments.clone(), arguments);
-                path_vals.extend(path.definitions.clone());
-                entity.velocity_fn = Some(path_vals);
-            }
+        if let Some((path, arguments)) = Entity::path_call(values.get("velocity_fn"), paths) {
+            let mut path_vals = Entity::align_function_args(&path.arguments, arguments);
+            Entity::draw_random_arguments(&mut path_vals, &values);
+            path_vals.extend(path.definitions.clone());
+            entity.velocity_fn = Some(path_vals);
         }
         if let Some(position) = values.get("position") {
             entity.position = match position.clone().eval(&values) {
@@ -276,6 +655,96 @@ impl<'a> Entity {
                 _ => Some(10.0),
             }
         }
+        // motion, integrated every frame by the interpreter
+        if let Some(acceleration) = values.get("acceleration") {
+            entity.motion.acceleration = match acceleration.clone().eval(&values) {
+                Ok(Primitive::I64(i)) => Some(Acceleration::Along(i as f64)),
+                Ok(Primitive::F64(f)) => Some(Acceleration::Along(f)),
+                _ => Entity::extract_pair(acceleration, &values).map(Acceleration::Toward),
+            }
+        }
+        if let Some(angular_velocity) = values.get("angular_velocity") {
+            entity.motion.angular_velocity =
+                Entity::extract_number(angular_velocity, &values).unwrap_or(0.0);
+        }
+        if let Some(max_speed) = values.get("max_speed") {
+            entity.motion.max_speed = Entity::extract_number(max_speed, &values);
+        }
+        if let Some(min_speed) = values.get("min_speed") {
+            entity.motion.min_speed = Entity::extract_number(min_speed, &values);
+        }
+        if let Some(friction) = values.get("friction") {
+            entity.motion.friction = Entity::extract_number(friction, &values).unwrap_or(0.0);
+        }
+        if let Some(curve) = values.get("speed_over_time") {
+            // t is left for each frame to fill in
+            entity.motion.speed_over_time = bytecode::compile(curve, &values, &["t"]).ok();
+        }
+        if let Some(behavior) = values.get("behavior") {
+            if let Some(behavior) = Entity::behavior_call(behavior, patterns, &values) {
+                entity.behavior = behavior;
+            }
+        }
+        // appearance
+        if let Some(sprite) = values.get("sprite") {
+            entity.sprite = Entity::extract_name(sprite, &values);
+        }
+        if let Some(color) = values.get("color") {
+            entity.color = Entity::extract_color(color, &values);
+        }
+        // alpha on its own overrides the color's, out of 255 or 1 like the color
+        if let Some(alpha) = values.get("alpha") {
+            match alpha.clone().eval(&values) {
+                Ok(Primitive::I64(i)) => entity.color.w = i.clamp(0, 255) as u8,
+                Ok(Primitive::F64(f)) => entity.color.w = (f * 255.).clamp(0., 255.) as u8,
+                _ => {}
+            }
+        }
+        if let Some(scale) = values.get("scale") {
+            entity.scale = Entity::extract_number(scale, &values).unwrap_or(1.0);
+        }
+        if let Some(z_order) = values.get("z_order") {
+            entity.z_order = Entity::extract_number(z_order, &values).unwrap_or(0.0) as i32;
+        }
+        if let Some(mode) = values
+            .get("blend")
+            .and_then(|blend| Entity::extract_name(blend, &values))
+            .and_then(|name| BlendMode::from_name(&name))
+        {
+            entity.blend = mode;
+        }
+        // hitbox
+        if let Some((size, offset)) = values
+            .get("hitbox")
+            .and_then(|hitbox| Entity::extract_hitbox(hitbox, &values))
+        {
+            entity.hitbox.size = Vector2::new(
+                size.x.round().max(0.0) as u16,
+                size.y.round().max(0.0) as u16,
+            );
+            if let Some(offset) = offset {
+                entity.hitbox.offset = offset;
+            }
+        }
+        if let Some(shape) = values
+            .get("shape")
+            .and_then(|shape| Entity::extract_name(shape, &values))
+            .and_then(|name| HitboxType::from_name(&name))
+        {
+            entity.hitbox.hitbox_type = shape;
+        }
+        if let Some(bounds) = values.get("bounds") {
+            let margin = match values.get("bounds_margin").map(|m| m.clone().eval(&values)) {
+                Some(Ok(Primitive::I64(i))) => i as f64,
+                Some(Ok(Primitive::F64(f))) => f,
+                _ => BoundsPolicy::DEFAULT_MARGIN,
+            };
+            if let Ok(Primitive::String(name)) = bounds.clone().eval(&values) {
+                if let Some(policy) = BoundsPolicy::from_name(&name, margin) {
+                    entity.bounds = policy;
+                }
+            }
+        }
         entity
     }
 }
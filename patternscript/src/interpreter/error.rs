@@ -1,25 +1,70 @@
 use super::primitive::Primitive;
-use crate::parser::types::Op;
+use crate::parser::types::{Op, Span};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum RuntimeError {
     #[error("Generic runtime error")]
     Generic,
-    #[error("Variable not defined: {0}")]
-    VarNotDef(String),
-    #[error("Cant compute runtime value of this type")]
-    ComputeTypeError,
-    #[error("Cannot negate non-integer type")]
-    NegateNonInt,
+    #[error("{1}: Variable not defined: {0}")]
+    VarNotDef(String, Span),
+    #[error("{0}: Cant compute runtime value of this type")]
+    ComputeTypeError(Span),
+    #[error("{0}: Cannot negate non-integer type")]
+    NegateNonInt(Span),
     #[error("Vector arithmetic typing error")]
     VecArithTypeError,
-    #[error("Vector types can only be int/float/string.")]
-    VecTypeError,
-    #[error("Type error: Operator {0:?} not defined for types {1:?} and {2:?}")]
-    OperatorTypeError(Op, Primitive, Primitive),
-    #[error("Conditional didn't evaluate to boolean type")]
-    CondNotBoolError,
+    #[error("{2}: Vectors of different lengths, {0} and {1}")]
+    VecLengthMismatch(usize, usize, Span),
+    #[error("{0}: Vector types can only be int/float/string.")]
+    VecTypeError(Span),
+    #[error("{3}: Type error: Operator {0:?} not defined for types {1:?} and {2:?}")]
+    OperatorTypeError(Op, Primitive, Primitive, Span),
+    #[error("{0}: Division by zero")]
+    DivideByZero(Span),
+    #[error("{1}: Integer overflow in {0:?}")]
+    IntOverflow(Op, Span),
+    #[error("{0}: Conditional didn't evaluate to boolean type")]
+    CondNotBoolError(Span),
+    #[error("{1}: Definition of {0} refers to itself")]
+    RecursiveDefinition(String, Span),
+    #[error("Builtin {0} expects {1}")]
+    BuiltinArgument(String, &'static str),
+    #[error("Builtin {0} takes {1} arguments, found {2}")]
+    BuiltinArity(String, String, usize),
+    #[error("Unknown function {0}")]
+    UnknownFunction(String),
+    #[error("Function {0} takes {1} arguments, found {2}")]
+    FunctionArity(String, usize, usize),
+    #[error("Function {0} called itself more than {1} calls deep")]
+    RecursionLimit(String, usize),
+    #[error("{1}: Function {0} can only pass around numbers, strings and vectors")]
+    FunctionValue(String, Span),
+    #[error("{1}: Function {0} can only assign and return")]
+    FunctionStatement(String, Span),
+    #[error("{1}: Function {0} ended without returning")]
+    NoReturn(String, Span),
     #[error("Pattern needs iteration_type specified.")]
     PatternIterationType,
 }
+
+impl RuntimeError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::VarNotDef(_, s)
+            | RuntimeError::RecursiveDefinition(_, s)
+            | RuntimeError::FunctionValue(_, s)
+            | RuntimeError::FunctionStatement(_, s)
+            | RuntimeError::NoReturn(_, s)
+            | RuntimeError::ComputeTypeError(s)
+            | RuntimeError::NegateNonInt(s)
+            | RuntimeError::DivideByZero(s)
+            | RuntimeError::IntOverflow(_, s)
+            | RuntimeError::VecTypeError(s)
+            | RuntimeError::VecLengthMismatch(_, _, s)
+            | RuntimeError::OperatorTypeError(_, _, _, s)
+            | RuntimeError::CondNotBoolError(s) => Some(*s),
+            _ => None,
+        }
+    }
+}
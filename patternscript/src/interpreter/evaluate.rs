@@ -2,8 +2,8 @@ use super::error::*;
 use super::primitive::*;
 use super::ps_funcs;
 use super::*;
-use crate::parser::types::Op;
-use anyhow::{Context, Result};
+use crate::parser::types::{Op, Span};
+use anyhow::Result;
 
 pub trait Evaluate {
     fn eval(self, v: &Values) -> Result<Primitive>;
@@ -11,203 +11,195 @@ pub trait Evaluate {
 
 impl Evaluate for ArithmeticExpression {
     fn eval(self, v: &Values) -> Result<Primitive> {
-        use Primitive::*;
         match self {
             ArithmeticExpression::Unary(op, val) => match op {
-                UnaryOperator::FunctionCall(ref fn_name) => {
-                    // todo: defining builtin funcs should be broken into their own file
-                    // todo: all functions return 0.0f
-                    ps_funcs::dispatch_func(fn_name.clone(), v, val)
+                UnaryOperator::Negate => {
+                    let span = val.span;
+                    negate((*val).eval(v)?, span)
                 }
-                UnaryOperator::Negate => match (*val).eval(v)? {
-                    F64(f) => Ok(F64(-1.0 * f)),
-                    I64(i) => Ok(I64(-1 * i)),
-                    _ => Err(RuntimeError::NegateNonInt.into()),
-                },
-            },
-            ArithmeticExpression::Binary(op, lhs, rhs) => match op {
-                // for repeated inner functions, they have to be repeated so that the inner typing is different
-                // match arms need the same types
-                // todo: refactor for macros at some point?
-                Op::Add => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(I64(l + r)),
-                    (F64(l), F64(r)) => Ok(F64(l + r)),
-                    (I64(i), F64(f)) | (F64(f), I64(i)) => Ok(F64(i as f64 + f)),
-                    (Primitive::String(l), Primitive::String(r)) => Ok(Primitive::String(l + &r)),
-                    // for any combination of l, r are either intvec or floatvec
-                    (l, r)
-                        if (matches!(l, Primitive::IntVec(_) | Primitive::FloatVec(_))
-                            && matches!(r, Primitive::IntVec(_) | Primitive::FloatVec(_))) =>
-                    {
-                        primitive_vec_arithmetic(PrimitiveVecOp::Add, l, r)
-                    }
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::Sub => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(I64(l - r)),
-                    (F64(l), F64(r)) => Ok(F64(l - r)),
-                    (I64(i), F64(f)) | (F64(f), I64(i)) => Ok(F64(i as f64 - f)),
-                    (l, r)
-                        if (matches!(l, Primitive::IntVec(_) | Primitive::FloatVec(_))
-                            && matches!(r, Primitive::IntVec(_) | Primitive::FloatVec(_))) =>
-                    {
-                        primitive_vec_arithmetic(PrimitiveVecOp::Sub, l, r)
-                    }
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::Mul => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(I64(l * r)),
-                    (F64(l), F64(r)) => Ok(F64(l * r)),
-                    (I64(i), F64(f)) | (F64(f), I64(i)) => Ok(F64(i as f64 * f)),
-                    (l, r)
-                        if (matches!(l, Primitive::IntVec(_) | Primitive::FloatVec(_))
-                            && matches!(r, Primitive::IntVec(_) | Primitive::FloatVec(_))) =>
-                    {
-                        primitive_vec_arithmetic(PrimitiveVecOp::Mul, l, r)
-                    }
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::Div => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(I64(l / r)),
-                    (F64(l), F64(r)) => Ok(F64(l / r)),
-                    (I64(i), F64(f)) | (F64(f), I64(i)) => Ok(F64(i as f64 / f)),
-                    (l, r)
-                        if (matches!(l, Primitive::IntVec(_) | Primitive::FloatVec(_))
-                            && matches!(r, Primitive::IntVec(_) | Primitive::FloatVec(_))) =>
-                    {
-                        primitive_vec_arithmetic(PrimitiveVecOp::Div, l, r)
-                    }
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::Exp => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(I64(l.pow(r.try_into().unwrap()))),
-                    (F64(l), F64(r)) => Ok(F64(l.powf(r))),
-                    (I64(l), F64(r)) => Ok(F64((l as f64).powf(r))),
-                    (F64(l), I64(r)) => Ok(F64(l.powf(r as f64))),
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::And => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (Bool(l), Bool(r)) => Ok(Bool(l && r)),
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::Or => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (Bool(l), Bool(r)) => Ok(Bool(l || r)),
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::Test => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(Bool(l == r)),
-                    (F64(l), F64(r)) => Ok(Bool(l == r)),
-                    (I64(l), F64(r)) => Ok(Bool(l as f64 == r)),
-                    (F64(l), I64(r)) => Ok(Bool(l == r as f64)),
-                    (Bool(l), Bool(r)) => Ok(Bool(l == r)),
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::GT => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(Bool(l > r)),
-                    (F64(l), F64(r)) => Ok(Bool(l > r)),
-                    (I64(l), F64(r)) => Ok(Bool(l as f64 > r)),
-                    (F64(l), I64(r)) => Ok(Bool(l > r as f64)),
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::GTE => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(Bool(l >= r)),
-                    (F64(l), F64(r)) => Ok(Bool(l >= r)),
-                    (I64(l), F64(r)) => Ok(Bool(l as f64 >= r)),
-                    (F64(l), I64(r)) => Ok(Bool(l >= r as f64)),
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::LT => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(Bool(l < r)),
-                    (F64(l), F64(r)) => Ok(Bool(l < r)),
-                    (I64(l), F64(r)) => Ok(Bool((l as f64) < r)),
-                    (F64(l), I64(r)) => Ok(Bool(l < r as f64)),
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
-                Op::LTE => match (lhs.eval(v)?, rhs.eval(v)?) {
-                    (I64(l), I64(r)) => Ok(Bool(l <= r)),
-                    (F64(l), F64(r)) => Ok(Bool(l <= r)),
-                    (I64(l), F64(r)) => Ok(Bool((l as f64) <= r)),
-                    (F64(l), I64(r)) => Ok(Bool(l <= r as f64)),
-                    (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r).into()),
-                },
             },
+            ArithmeticExpression::Call(fn_name, args) => ps_funcs::dispatch_func(&fn_name, v, args),
+            ArithmeticExpression::Binary(op, lhs, rhs) => {
+                let span = lhs.span.to(rhs.span);
+                binary_op(op, lhs.eval(v)?, rhs.eval(v)?, span)
+            }
         }
     }
 }
+
+// operators on already evaluated operands, shared by the tree walker and the vm
+
+pub fn negate(val: Primitive, span: Span) -> Result<Primitive> {
+    use Primitive::*;
+    match val {
+        F64(f) => Ok(F64(-f)),
+        I64(i) => checked(i.checked_neg(), Op::Sub, span),
+        _ => Err(RuntimeError::NegateNonInt(span).into()),
+    }
+}
+
+// integer arithmetic that went past what an i64 holds
+fn checked(result: Option<i64>, op: Op, span: Span) -> Result<Primitive> {
+    result
+        .map(Primitive::I64)
+        .ok_or_else(|| RuntimeError::IntOverflow(op, span).into())
+}
+
+pub fn binary_op(op: Op, lhs: Primitive, rhs: Primitive, span: Span) -> Result<Primitive> {
+    use Primitive::*;
+    match op {
+        // for repeated inner functions, they have to be repeated so that the inner typing is different
+        // match arms need the same types
+        // todo: refactor for macros at some point?
+        Op::Add => match (lhs, rhs) {
+            (I64(l), I64(r)) => checked(l.checked_add(r), op, span),
+            (F64(l), F64(r)) => Ok(F64(l + r)),
+            (I64(i), F64(f)) | (F64(f), I64(i)) => Ok(F64(i as f64 + f)),
+            (Primitive::String(l), Primitive::String(r)) => Ok(Primitive::String(l + &r)),
+            // for any combination of l, r are either intvec or floatvec
+            (l, r)
+                if (matches!(l, Primitive::IntVec(_) | Primitive::FloatVec(_))
+                    && matches!(r, Primitive::IntVec(_) | Primitive::FloatVec(_))) =>
+            {
+                primitive_vec_arithmetic(PrimitiveVecOp::Add, l, r, span)
+            }
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::Sub => match (lhs, rhs) {
+            (I64(l), I64(r)) => checked(l.checked_sub(r), op, span),
+            (F64(l), F64(r)) => Ok(F64(l - r)),
+            (I64(l), F64(r)) => Ok(F64(l as f64 - r)),
+            (F64(l), I64(r)) => Ok(F64(l - r as f64)),
+            (l, r)
+                if (matches!(l, Primitive::IntVec(_) | Primitive::FloatVec(_))
+                    && matches!(r, Primitive::IntVec(_) | Primitive::FloatVec(_))) =>
+            {
+                primitive_vec_arithmetic(PrimitiveVecOp::Sub, l, r, span)
+            }
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::Mul => match (lhs, rhs) {
+            (I64(l), I64(r)) => checked(l.checked_mul(r), op, span),
+            (F64(l), F64(r)) => Ok(F64(l * r)),
+            (I64(i), F64(f)) | (F64(f), I64(i)) => Ok(F64(i as f64 * f)),
+            (l, r)
+                if (matches!(l, Primitive::IntVec(_) | Primitive::FloatVec(_))
+                    && matches!(r, Primitive::IntVec(_) | Primitive::FloatVec(_))) =>
+            {
+                primitive_vec_arithmetic(PrimitiveVecOp::Mul, l, r, span)
+            }
+            // scaling works from either side
+            (s @ (I64(_) | F64(_)), v @ (IntVec(_) | FloatVec(_)))
+            | (v @ (IntVec(_) | FloatVec(_)), s @ (I64(_) | F64(_))) => {
+                primitive_vec_scale(PrimitiveVecOp::Mul, v, s)
+            }
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::Div => match (lhs, rhs) {
+            (I64(_), I64(0)) => Err(RuntimeError::DivideByZero(span).into()),
+            (I64(l), I64(r)) => checked(l.checked_div(r), op, span),
+            (F64(l), F64(r)) => Ok(F64(l / r)),
+            (I64(l), F64(r)) => Ok(F64(l as f64 / r)),
+            (F64(l), I64(r)) => Ok(F64(l / r as f64)),
+            (l, r)
+                if (matches!(l, Primitive::IntVec(_) | Primitive::FloatVec(_))
+                    && matches!(r, Primitive::IntVec(_) | Primitive::FloatVec(_))) =>
+            {
+                primitive_vec_arithmetic(PrimitiveVecOp::Div, l, r, span)
+            }
+            // but a number can't be divided by a vector
+            (v @ (IntVec(_) | FloatVec(_)), s @ (I64(_) | F64(_))) => {
+                primitive_vec_scale(PrimitiveVecOp::Div, v, s)
+            }
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::Exp => match (lhs, rhs) {
+            // a negative power is a fraction, so it's promoted like mixed ints and floats are
+            (I64(l), I64(r)) if r < 0 => Ok(F64((l as f64).powf(r as f64))),
+            (I64(l), I64(r)) => checked(
+                u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+                op,
+                span,
+            ),
+            (F64(l), F64(r)) => Ok(F64(l.powf(r))),
+            (I64(l), F64(r)) => Ok(F64((l as f64).powf(r))),
+            (F64(l), I64(r)) => Ok(F64(l.powf(r as f64))),
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::And => match (lhs, rhs) {
+            (Bool(l), Bool(r)) => Ok(Bool(l && r)),
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::Or => match (lhs, rhs) {
+            (Bool(l), Bool(r)) => Ok(Bool(l || r)),
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::Test => match (lhs, rhs) {
+            (I64(l), I64(r)) => Ok(Bool(l == r)),
+            (F64(l), F64(r)) => Ok(Bool(l == r)),
+            (I64(l), F64(r)) => Ok(Bool(l as f64 == r)),
+            (F64(l), I64(r)) => Ok(Bool(l == r as f64)),
+            (Bool(l), Bool(r)) => Ok(Bool(l == r)),
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::GT => match (lhs, rhs) {
+            (I64(l), I64(r)) => Ok(Bool(l > r)),
+            (F64(l), F64(r)) => Ok(Bool(l > r)),
+            (I64(l), F64(r)) => Ok(Bool(l as f64 > r)),
+            (F64(l), I64(r)) => Ok(Bool(l > r as f64)),
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::GTE => match (lhs, rhs) {
+            (I64(l), I64(r)) => Ok(Bool(l >= r)),
+            (F64(l), F64(r)) => Ok(Bool(l >= r)),
+            (I64(l), F64(r)) => Ok(Bool(l as f64 >= r)),
+            (F64(l), I64(r)) => Ok(Bool(l >= r as f64)),
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::LT => match (lhs, rhs) {
+            (I64(l), I64(r)) => Ok(Bool(l < r)),
+            (F64(l), F64(r)) => Ok(Bool(l < r)),
+            (I64(l), F64(r)) => Ok(Bool((l as f64) < r)),
+            (F64(l), I64(r)) => Ok(Bool(l < r as f64)),
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+        Op::LTE => match (lhs, rhs) {
+            (I64(l), I64(r)) => Ok(Bool(l <= r)),
+            (F64(l), F64(r)) => Ok(Bool(l <= r)),
+            (I64(l), F64(r)) => Ok(Bool((l as f64) <= r)),
+            (F64(l), I64(r)) => Ok(Bool(l <= r as f64)),
+            (l, r) => Err(RuntimeError::OperatorTypeError(op, l, r, span).into()),
+        },
+    }
+}
+
 impl Evaluate for ExpressionType {
     fn eval(self, v: &Values) -> Result<Primitive> {
-        match self {
-            ExpressionType::String(s) => Ok(Primitive::String(s)),
-            ExpressionType::Float(f) => Ok(Primitive::F64(f)),
-            ExpressionType::Int(i) => Ok(Primitive::I64(i)),
-            ExpressionType::Variable(var) => v
-                .get(&var)
-                .context(format!("Variable Not Defined: {}", var))?
-                .clone()
-                .eval(v),
-            ExpressionType::Expr(e) => e.eval(v),
-            // TODO: refactor this? would love to specialize it just on some types and not all
-            ExpressionType::Vector(vec) => {
-                // empty vectors can't exist in the parser, i think
-                match &vec.first().unwrap() {
-                    // check first element for type: they must be homogenous
-                    // would this be better as a macro?
-                    ExpressionType::Int(_i) => {
-                        let mut primitive_vec: Vec<i64> = Vec::new();
-                        for element in vec {
-                            if let Primitive::I64(inner) = element.eval(v)? {
-                                primitive_vec.push(inner);
-                            }
-                        }
-                        Ok(Primitive::IntVec(primitive_vec))
-                    }
-                    ExpressionType::Float(_f) => {
-                        let mut primitive_vec: Vec<f64> = Vec::new();
-                        for element in vec {
-                            if let Primitive::F64(inner) = element.eval(v)? {
-                                primitive_vec.push(inner);
-                            }
-                        }
-                        Ok(Primitive::FloatVec(primitive_vec))
-                    }
-                    ExpressionType::String(_s) => {
-                        let mut primitive_vec: Vec<String> = Vec::new();
-                        for element in vec {
-                            if let Primitive::String(inner) = element.eval(v)? {
-                                primitive_vec.push(inner);
-                            }
-                        }
-
-                        Ok(Primitive::StrVec(primitive_vec))
-                    }
-                    ExpressionType::Expr(e) => match e.clone().eval(v)? {
-                        Primitive::I64(_i) => {
-                            let mut primitive_vec: Vec<i64> = Vec::new();
-                            for element in vec {
-                                if let Primitive::I64(inner) = element.eval(v)? {
-                                    primitive_vec.push(inner);
-                                }
-                            }
-                            Ok(Primitive::IntVec(primitive_vec))
-                        }
-                        Primitive::F64(_f) => {
-                            let mut primitive_vec: Vec<f64> = Vec::new();
-                            for element in vec {
-                                if let Primitive::F64(inner) = element.eval(v)? {
-                                    primitive_vec.push(inner);
-                                }
-                            }
-                            Ok(Primitive::FloatVec(primitive_vec))
-                        }
-                        _ => Err(RuntimeError::VecTypeError.into()),
-                    },
-                    _ => Err(RuntimeError::VecTypeError.into()),
-                }
+        let span = self.span;
+        match self.kind {
+            ExpressionKind::String(s) => Ok(Primitive::String(s)),
+            ExpressionKind::Float(f) => Ok(Primitive::F64(f)),
+            ExpressionKind::Int(i) => Ok(Primitive::I64(i)),
+            ExpressionKind::Variable(var) => match v.get(&var) {
+                Some(e) => e.clone().eval(v),
+                None => match ps_funcs::constant(&var) {
+                    Some(c) => Ok(Primitive::F64(c)),
+                    None => Err(RuntimeError::VarNotDef(var, span).into()),
+                },
+            },
+            ExpressionKind::Expr(e) => e.eval(v),
+            ExpressionKind::Vector(vec) => {
+                // elements must be homogenous: ints, floats (ints promoted), or strings
+                let elements = vec
+                    .into_iter()
+                    .map(|e| e.eval(v))
+                    .collect::<Result<Vec<Primitive>>>()?;
+                primitive_vector(elements, span)
             }
-            ExpressionType::Block(_)
-            | ExpressionType::Duration(_)
-            | ExpressionType::Range(..)
-            | ExpressionType::None => Err(RuntimeError::ComputeTypeError.into()),
+            ExpressionKind::Block(_)
+            | ExpressionKind::Duration(_)
+            | ExpressionKind::Range(..)
+            | ExpressionKind::None => Err(RuntimeError::ComputeTypeError(span).into()),
         }
     }
 }
@@ -218,19 +210,71 @@ impl Evaluate for Condition {
         match self {
             Condition::None => Ok(Bool(true)),
             Condition::When(e) => {
+                let span = e.span;
                 if let Bool(b) = e.eval(v)? {
                     Ok(Bool(b))
                 } else {
-                    Err(RuntimeError::CondNotBoolError.into())
+                    Err(RuntimeError::CondNotBoolError(span).into())
                 }
             }
             Condition::Unless(e) => {
+                let span = e.span;
                 if let Bool(b) = e.eval(v)? {
                     Ok(Bool(!b))
                 } else {
-                    Err(RuntimeError::CondNotBoolError.into())
+                    Err(RuntimeError::CondNotBoolError(span).into())
                 }
             }
         }
     }
 }
+
+// replace every variable bound in scope with its bound expression
+//   unbound variables (globals, path arguments) are left to be looked up at evaluation
+pub fn substitute(expr: &ExpressionType, scope: &Values) -> ExpressionType {
+    let kind = match &expr.kind {
+        ExpressionKind::Variable(var) => match scope.get(var) {
+            Some(bound) => return bound.clone(),
+            None => ExpressionKind::Variable(var.clone()),
+        },
+        ExpressionKind::Expr(ArithmeticExpression::Unary(op, e)) => ExpressionKind::Expr(
+            ArithmeticExpression::Unary(op.clone(), Box::new(substitute(e, scope))),
+        ),
+        ExpressionKind::Expr(ArithmeticExpression::Binary(op, l, r)) => {
+            ExpressionKind::Expr(ArithmeticExpression::Binary(
+                op.clone(),
+                Box::new(substitute(l, scope)),
+                Box::new(substitute(r, scope)),
+            ))
+        }
+        ExpressionKind::Expr(ArithmeticExpression::Call(fn_name, args)) => {
+            ExpressionKind::Expr(ArithmeticExpression::Call(
+                fn_name.clone(),
+                args.iter().map(|a| substitute(a, scope)).collect(),
+            ))
+        }
+        ExpressionKind::Vector(v) => {
+            ExpressionKind::Vector(v.iter().map(|e| substitute(e, scope)).collect())
+        }
+        ExpressionKind::Duration(wd) => ExpressionKind::Duration(Box::new(match wd.as_ref() {
+            WaitData::Frames(e) => WaitData::Frames(substitute(e, scope)),
+            WaitData::Time(e) => WaitData::Time(substitute(e, scope)),
+        })),
+        other => other.clone(),
+    };
+    ExpressionType::new(kind, expr.span)
+}
+
+// whether an expression gives the same value every time it's evaluated in the same scope
+//   false when it calls a random builtin somewhere; variables aren't followed
+pub fn is_pure(expr: &ExpressionType) -> bool {
+    match &expr.kind {
+        ExpressionKind::Expr(ArithmeticExpression::Unary(_, e)) => is_pure(e),
+        ExpressionKind::Expr(ArithmeticExpression::Call(fn_name, args)) => {
+            ps_funcs::is_pure(fn_name) && args.iter().all(is_pure)
+        }
+        ExpressionKind::Expr(ArithmeticExpression::Binary(_, l, r)) => is_pure(l) && is_pure(r),
+        ExpressionKind::Vector(v) => v.iter().all(is_pure),
+        _ => true,
+    }
+}
@@ -0,0 +1,189 @@
+use super::entity::Entity;
+use super::error::RuntimeError;
+use super::evaluate::Evaluate;
+use super::primitive::Primitive;
+use super::ps_funcs;
+use crate::parser::parser::*;
+use crate::parser::types::Span;
+use anyhow::Result;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+// how deep calls to functions defined with fn can nest before giving up
+//   there are no conditionals yet, so recursion only ends here
+pub const MAX_CALL_DEPTH: usize = 64;
+
+pub type FunctionMap = HashMap<String, Function>;
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub definition: FunctionData,
+    // false when it calls a random builtin, directly or through another function
+    pub pure: bool,
+}
+
+impl Function {
+    pub fn new(definition: &FunctionData) -> Self {
+        Function {
+            definition: definition.clone(),
+            pure: true,
+        }
+    }
+
+    pub fn parameters(&self) -> usize {
+        match &self.definition.arguments.kind {
+            ExpressionKind::Vector(parameters) => parameters.len(),
+            _ => 0,
+        }
+    }
+
+    fn call(&self, fn_name: &str, args: Vec<Primitive>) -> Result<Primitive> {
+        if args.len() != self.parameters() {
+            return Err(RuntimeError::FunctionArity(
+                fn_name.to_string(),
+                self.parameters(),
+                args.len(),
+            )
+            .into());
+        }
+        // arguments are already evaluated in the caller's scope, the body only sees them
+        let arguments = args
+            .into_iter()
+            .map(|arg| literal(fn_name, arg, Span::default()))
+            .collect::<Result<Vec<ExpressionType>>>()?;
+        let values = Entity::align_function_args(&self.definition.arguments, &arguments);
+        match &self.definition.body.kind {
+            ExpressionKind::Block(block) => run_block(fn_name, block, values),
+            _ => self.definition.body.clone().eval(&values),
+        }
+    }
+}
+
+// assignments in order, up to the first return
+fn run_block(fn_name: &str, block: &Block, mut values: Values) -> Result<Primitive> {
+    for statement in &block.statements {
+        match statement {
+            Node::Assign(ad) => {
+                // evaluated now, so `x = x + 1` reads the previous x
+                let value = ad.rvalue.clone().eval(&values)?;
+                values.insert(ad.lvalue.clone(), literal(fn_name, value, ad.span)?);
+            }
+            Node::Return(rd) => return rd.value.clone().eval(&values),
+            other => {
+                return Err(
+                    RuntimeError::FunctionStatement(fn_name.to_string(), other.span()).into(),
+                )
+            }
+        }
+    }
+    Err(RuntimeError::NoReturn(fn_name.to_string(), block.span).into())
+}
+
+// values are bound as literals, which booleans don't have
+fn literal(fn_name: &str, value: Primitive, span: Span) -> Result<ExpressionType> {
+    value
+        .into_expression(span)
+        .ok_or_else(|| RuntimeError::FunctionValue(fn_name.to_string(), span).into())
+}
+
+// every function and builtin called somewhere in an expression
+fn calls<'e>(expr: &'e ExpressionType, found: &mut Vec<&'e str>) {
+    match &expr.kind {
+        ExpressionKind::Expr(ArithmeticExpression::Unary(_, e)) => calls(e, found),
+        ExpressionKind::Expr(ArithmeticExpression::Call(fn_name, args)) => {
+            found.push(fn_name);
+            args.iter().for_each(|e| calls(e, found));
+        }
+        ExpressionKind::Expr(ArithmeticExpression::Binary(_, l, r)) => {
+            calls(l, found);
+            calls(r, found);
+        }
+        ExpressionKind::Vector(v) => v.iter().for_each(|e| calls(e, found)),
+        ExpressionKind::Block(block) => {
+            for statement in &block.statements {
+                match statement {
+                    Node::Assign(ad) => calls(&ad.rvalue, found),
+                    Node::Return(rd) => calls(&rd.value, found),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// a function is impure if anything it calls is; repeated until that stops spreading, which
+// also settles functions calling each other
+pub fn mark_impure(functions: &mut FunctionMap) {
+    loop {
+        let impure: Vec<String> = functions
+            .iter()
+            .filter(|(_, f)| f.pure)
+            .filter(|(_, f)| {
+                let mut called = Vec::new();
+                calls(&f.definition.body, &mut called);
+                called.into_iter().any(|fn_name| {
+                    ps_funcs::IMPURE_BUILTINS.contains(&fn_name)
+                        || functions.get(fn_name).is_some_and(|g| !g.pure)
+                })
+            })
+            .map(|(fn_name, _)| fn_name.clone())
+            .collect();
+        if impure.is_empty() {
+            return;
+        }
+        for fn_name in impure {
+            if let Some(f) = functions.get_mut(&fn_name) {
+                f.pure = false;
+            }
+        }
+    }
+}
+
+// like the random generator, the interpreter lends its functions to the thread for the
+// length of a step so calls deep inside Evaluate::eval can find them
+thread_local! {
+    static ACTIVE: RefCell<FunctionMap> = RefCell::new(HashMap::new());
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+// exchange functions with the active ones: once to lend them out, once more to take them back
+fn swap_active(functions: &mut FunctionMap) {
+    ACTIVE.with(|active| std::mem::swap(functions, &mut active.borrow_mut()));
+}
+
+// functions lent to the thread for as long as this lives, taken back on drop like random::Lent
+pub(crate) struct Lent<'a>(&'a mut FunctionMap);
+
+impl<'a> Lent<'a> {
+    pub(crate) fn new(functions: &'a mut FunctionMap) -> Self {
+        swap_active(functions);
+        Lent(functions)
+    }
+}
+
+impl Drop for Lent<'_> {
+    fn drop(&mut self) {
+        swap_active(self.0);
+    }
+}
+
+// true for anything that isn't an impure function, builtins are checked by ps_funcs
+pub fn is_pure(fn_name: &str) -> bool {
+    ACTIVE.with(|active| active.borrow().get(fn_name).is_none_or(|f| f.pure))
+}
+
+pub fn call(fn_name: &str, args: Vec<Primitive>) -> Result<Primitive> {
+    // cloned out so the body can call other functions while it runs
+    let function = ACTIVE
+        .with(|active| active.borrow().get(fn_name).cloned())
+        .ok_or_else(|| RuntimeError::UnknownFunction(fn_name.to_string()))?;
+    let depth = DEPTH.with(|depth| depth.get());
+    if depth >= MAX_CALL_DEPTH {
+        return Err(RuntimeError::RecursionLimit(fn_name.to_string(), MAX_CALL_DEPTH).into());
+    }
+    DEPTH.with(|d| d.set(depth + 1));
+    let result = function.call(fn_name, args);
+    DEPTH.with(|d| d.set(depth));
+    result
+}
@@ -1,20 +1,36 @@
-pub mod callback;
+pub mod bounds;
+pub mod bytecode;
+pub mod collision;
+pub mod coroutine;
+pub mod curves;
 pub mod entity;
 pub mod error;
 pub mod evaluate;
+pub mod functions;
+pub mod motion;
 pub mod primitive;
 pub mod ps_funcs;
+pub mod random;
+pub mod spatial;
+pub mod target;
 mod utils;
+pub mod vm;
 
-use self::evaluate::Evaluate;
 use self::primitive::Primitive;
 
 use super::parser::parser::*;
 use anyhow::Result;
-use callback::{Actions, CallbackResult};
+use bounds::Playfield;
+use bytecode::{PathProgram, PATH_INPUTS};
 use cgmath::{Angle, Vector2};
+use collision::{CollisionOptions, Hit};
+use coroutine::Coroutine;
 use entity::*;
+use functions::{Function, FunctionMap};
+use random::Rng;
+use spatial::SpatialHash;
 use std::collections::HashMap;
+use target::{Target, TargetId, TargetMap};
 use thiserror::Error;
 use utils::swap_remove_all;
 
@@ -23,6 +39,8 @@ use utils::swap_remove_all;
 pub enum IError {
     #[error("Parse of pattern did not result in head node.")]
     FromParse,
+    #[error("The {0} {1} extends itself.")]
+    ExtendsItself(&'static str, String),
 }
 
 type PathMap = HashMap<String, PathData>;
@@ -30,21 +48,96 @@ type EntityMap = HashMap<String, Entity>;
 type PatternMap = HashMap<String, PatternData>;
 type BulletMap = HashMap<String, BulletData>;
 
+// definitions that can start from another's, patterns with extends and bullets with :
+pub trait Inherits {
+    fn parent(&self) -> Option<&str>;
+}
+
+impl Inherits for PatternData {
+    fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+}
+
+impl Inherits for BulletData {
+    fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+}
+
+// a definition followed by the one it extends, and so on up, stopping at any that is missing
+// or already listed
+pub fn lineage<'d, T: Inherits>(name: &str, lookup: impl Fn(&str) -> Option<&'d T>) -> Vec<&'d T> {
+    let mut definitions: Vec<&T> = Vec::new();
+    let mut next = lookup(name);
+    while let Some(definition) = next {
+        if definitions.iter().any(|d| std::ptr::eq(*d, definition)) {
+            break;
+        }
+        definitions.push(definition);
+        next = definition.parent().and_then(&lookup);
+    }
+    definitions
+}
+
+// lineage stops before a definition comes up twice, which is this one if it is in a cycle
+pub fn extends_itself<'d, T: Inherits + 'd>(
+    name: &str,
+    lookup: impl Fn(&str) -> Option<&'d T>,
+) -> bool {
+    lineage(name, lookup).last().and_then(|last| last.parent()) == Some(name)
+}
+
+// one pattern out of a lineage: definitions override the ones they inherit, and parameters
+// come from the nearest pattern that declares any
+pub fn inherit(lineage: &[&PatternData]) -> PatternData {
+    let mut pattern = lineage[0].clone();
+    for ancestor in &lineage[1..] {
+        for (name, definition) in &ancestor.definitions {
+            pattern
+                .definitions
+                .entry(name.clone())
+                .or_insert_with(|| definition.clone());
+        }
+        let unset = matches!(&pattern.arguments.kind, ExpressionKind::Vector(p) if p.is_empty());
+        if unset {
+            pattern.arguments = ancestor.arguments.clone();
+        }
+    }
+    pattern
+}
+
 #[derive(Debug)]
-pub struct Interpreter<'a> {
+pub struct Interpreter {
     pub elapsed: u64,
     pub fps: u16,
     pub head: HeadData,
     pub entities: Vec<ExecutionEnvironment>,
-    pub actions: Actions<'a>,
+    // the running pattern of each entity, by index
+    pub actions: Vec<Option<Coroutine>>,
     pub paths: PathMap,
+    // defined with fn, callable from any expression
+    pub functions: FunctionMap,
     pub prefabs: EntityMap,
     pub patterns: PatternMap,
     pub bullets: BulletMap,
+    // player(s) the patterns aim at, kept up to date by the host
+    pub targets: TargetMap,
+    // entities that touched a target's hurtbox on the last step
+    pub hits: Vec<Hit>,
+    pub collision: CollisionOptions,
+    // where every entity is as of the end of the last step, for proximity queries
+    pub grid: SpatialHash,
+    // how far around the player and an entity the bullets_near globals look
+    pub proximity_radius: f64,
+    // bullets are kept to this according to their bounds policy, no limits when None
+    pub playfield: Option<Playfield>,
+    // what the rand builtins draw from, see set_seed
+    pub rng: Rng,
 }
 
-impl<'a> Interpreter<'a> {
-    pub fn new(hd: HeadData) -> Self {
+impl Interpreter {
+    pub fn new(hd: HeadData) -> Result<Self> {
         let mut i = Interpreter {
             elapsed: 0,
             fps: 120,
@@ -52,30 +145,66 @@ impl<'a> Interpreter<'a> {
             entities: Vec::new(),
             actions: Vec::new(),
             paths: HashMap::new(),
+            functions: HashMap::new(),
             prefabs: HashMap::new(),
             patterns: HashMap::new(),
             bullets: HashMap::new(),
+            targets: TargetMap::new(),
+            hits: Vec::new(),
+            collision: CollisionOptions::default(),
+            grid: SpatialHash::default(),
+            proximity_radius: 64.0,
+            playfield: None,
+            rng: Rng::default(),
         };
-        i.initialize();
-        i
+        i.initialize()?;
+        Ok(i)
     }
 
     pub fn from_parse_result(n: Node) -> Result<Self> {
         match n {
-            Node::Head(hd) => Ok(Interpreter::new(hd)),
+            Node::Head(hd) => Interpreter::new(hd),
             _ => Err(IError::FromParse.into()),
         }
     }
 
-    pub fn initialize(&mut self) {
+    /// Registers every definition in the file, failing if a pattern or bullet extends itself.
+    pub fn initialize(&mut self) -> Result<()> {
         for (k, v) in &self.head.definitions {
             match v {
                 Node::Path(pd) => Interpreter::register_path(k, &mut self.paths, pd),
                 Node::Pattern(pd) => Interpreter::register_pattern(k, &mut self.patterns, pd),
                 Node::Bullet(bd) => Interpreter::register_bullet(k, &mut self.bullets, bd),
+                Node::Function(fd) => Interpreter::register_function(k, &mut self.functions, fd),
                 _ => {}
             }
         }
+        functions::mark_impure(&mut self.functions);
+        if let Some(name) = self
+            .patterns
+            .keys()
+            .find(|name| extends_itself(name, |n| self.patterns.get(n)))
+        {
+            return Err(IError::ExtendsItself("pattern", name.clone()).into());
+        }
+        if let Some(name) = self
+            .bullets
+            .keys()
+            .find(|name| extends_itself(name, |n| self.bullets.get(n)))
+        {
+            return Err(IError::ExtendsItself("bullet", name.clone()).into());
+        }
+        self.patterns = self
+            .patterns
+            .keys()
+            .map(|name| {
+                (
+                    name.clone(),
+                    inherit(&lineage(name, |n| self.patterns.get(n))),
+                )
+            })
+            .collect();
+        Ok(())
     }
 
     fn register_path(name: &String, paths: &mut PathMap, pd: &PathData) {
@@ -83,6 +212,10 @@ impl<'a> Interpreter<'a> {
         paths.insert(name.clone(), pd.clone());
     }
 
+    fn register_function(name: &str, functions: &mut FunctionMap, fd: &FunctionData) {
+        functions.insert(name.to_string(), Function::new(fd));
+    }
+
     fn register_pattern(name: &String, patterns: &mut PatternMap, pd: &PatternData) {
         // todo: finish committing to map
         println!("registering pattern: {}", name);
@@ -97,19 +230,43 @@ impl<'a> Interpreter<'a> {
 
     pub fn spawn_direct(&mut self, entity: &Entity) {
         self.entities.push(ExecutionEnvironment::new(entity));
-        let globals = Interpreter::create_globals(entity.position);
-        if let Some(new_actions) = entity.compile_behavior(
-            &self.paths,
-            &self.patterns,
-            &self.prefabs,
-            &self.bullets,
-            globals,
-            self.fps,
-        ) {
-            self.actions.push(Some(new_actions));
-        } else {
-            self.actions.push(None);
-        }
+        self.actions
+            .push(entity.compile_behavior(&self.patterns, self.fps));
+    }
+
+    /// Registers a target, or moves it if `id` is already registered. Call every frame
+    /// before `step` for anything that moves; patterns aim at the target nearest to them.
+    pub fn set_target(
+        &mut self,
+        id: TargetId,
+        position: Vector2<f64>,
+        velocity: Option<Vector2<f64>>,
+    ) {
+        let target = self
+            .targets
+            .entry(id)
+            .or_insert_with(|| Target::new(position, velocity));
+        target.position = position;
+        target.velocity = velocity;
+    }
+
+    /// Gives a target a hurtbox, which bullets are tested against after every step.
+    /// The target is registered at the origin if it wasn't already.
+    pub fn set_hurtbox(&mut self, id: TargetId, hurtbox: Option<Hitbox>) {
+        self.targets
+            .entry(id)
+            .or_insert_with(|| Target::new(Vector2::new(0.0, 0.0), None))
+            .hurtbox = hurtbox;
+    }
+
+    /// Restarts the random builtins from `seed`. Two runs with the same seed, file and
+    /// host input make the same calls in the same order and so see the same numbers.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn remove_target(&mut self, id: TargetId) -> Option<Target> {
+        self.targets.remove(&id)
     }
 
     pub fn spawn_named(&mut self, name: String) {
@@ -117,164 +274,325 @@ impl<'a> Interpreter<'a> {
             .push(ExecutionEnvironment::new(&self.prefabs[&name]));
     }
 
-    pub fn move_entities(exec: &mut Vec<ExecutionEnvironment>, fps: u16) {
-        let get_primitive = |var: String, vals: &Values| -> Primitive {
-            vals.get(&var).unwrap().clone().eval(&vals).unwrap()
-        };
-        let extract_numeric = |primitive: Primitive| -> f64 {
-            return match primitive {
-                Primitive::I64(i) => i as f64,
-                Primitive::F64(f) => f,
-                _ => 0.0,
-            };
-        };
+    pub fn move_entities(exec: &mut Vec<ExecutionEnvironment>, targets: &TargetMap, fps: u16) {
         for environment in exec {
+            environment.previous_position = environment.entity.position;
             // is it rotation/speed or hard set pos/vel?
             // precedence:
             //   position_fn exists
             //   velocity_fn exists (set velocity, resolve position per frame)
+            //   motion changes speed/rotation, or velocity without a speed
             //   speed/rotation exist, resolve velocity, then resolve position from velocity
             //   resolve position from velocity
 
-            // contains patternscript globals to be added in
-            //   such as the entity position
-
-            //todo: instance overwrites locals, shouldn't
-            let mut vals: Values = environment
-                .entity
-                .instance_vars
-                .clone()
-                .unwrap_or(HashMap::new());
-            // time
-            vals.insert(
-                "t".to_string(),
-                ExpressionType::Int(environment.elapsed as i64),
-            );
-            // towards player
-            vals.insert(
-                "towards_player".to_string(),
-                Interpreter::angle_towards_player(),
-            );
+            // per frame inputs to path functions: time and the player as of this frame
+            let player = target::player_state(targets, environment.entity.position);
+            let inputs = [
+                Primitive::I64(environment.elapsed as i64),
+                Primitive::F64(player.angle),
+                Primitive::FloatVec(vec![player.position.x, player.position.y]),
+                Primitive::FloatVec(vec![player.velocity.x, player.velocity.y]),
+                Primitive::F64(player.distance),
+            ];
 
             if let Some(pos_fn) = &environment.entity.position_fn {
-                let mut fn_with_globals = pos_fn.clone();
-                fn_with_globals.extend(vals);
-                let x = extract_numeric(get_primitive("x".to_string(), &fn_with_globals));
-                let y = extract_numeric(get_primitive("y".to_string(), &fn_with_globals));
-                environment.entity.position = Vector2::new(x, y);
+                environment.entity.position = Interpreter::evaluate_path(
+                    &environment.position_program,
+                    &environment.entity.path_environment(pos_fn),
+                    &inputs,
+                );
             } else {
+                // a velocity_fn decides the velocity by itself
+                if environment.entity.velocity_fn.is_none() {
+                    environment
+                        .entity
+                        .integrate_motion(environment.elapsed, 1.0 / fps as f64);
+                }
                 if let Some(speed) = &environment.entity.speed {
                     let x = *speed * environment.entity.rotation.cos() as f64;
                     let y = *speed * environment.entity.rotation.sin() as f64;
                     environment.entity.velocity = Vector2::new(x, y);
                 }
                 if let Some(vel_fn) = &environment.entity.velocity_fn {
-                    let mut fn_with_globals = vel_fn.clone();
-                    fn_with_globals.extend(vals);
-                    let x = extract_numeric(get_primitive("x".to_string(), &fn_with_globals));
-                    let y = extract_numeric(get_primitive("y".to_string(), &fn_with_globals));
-                    environment.entity.velocity = Vector2::new(x, y);
+                    let velocity = Interpreter::evaluate_path(
+                        &environment.velocity_program,
+                        &environment.entity.path_environment(vel_fn),
+                        &inputs,
+                    );
+                    environment.entity.velocity = Vector2::new(
+                        velocity.x * environment.reflection.x,
+                        velocity.y * environment.reflection.y,
+                    );
                 }
 
                 environment.entity.position += environment.entity.velocity * (1.0 / fps as f64);
             }
-            //environment.elapsed += 1;
         }
     }
 
-    fn angle_towards_player() -> ExpressionType {
-        // todo: no player
-        ExpressionType::Float(0.0)
+    // x and y of a path function on the vm, or the tree walker if it didn't compile
+    //   debug builds also run the tree walker on compiled paths and check the two agree
+    fn evaluate_path(
+        program: &Option<PathProgram>,
+        env: &Values,
+        inputs: &[Primitive],
+    ) -> Vector2<f64> {
+        let extract_numeric = |primitive: Result<Primitive>| -> f64 {
+            match primitive {
+                Ok(Primitive::I64(i)) => i as f64,
+                Ok(Primitive::F64(f)) => f,
+                _ => 0.0,
+            }
+        };
+        let reference = || {
+            (
+                vm::reference(&PathProgram::x_expr(), env, &PATH_INPUTS, inputs),
+                vm::reference(&PathProgram::y_expr(), env, &PATH_INPUTS, inputs),
+            )
+        };
+        let (x, y) = match program {
+            Some(program) => {
+                let (x, y) = (
+                    vm::execute(&program.x, inputs),
+                    vm::execute(&program.y, inputs),
+                );
+                // running it twice would draw extra random numbers, and different ones
+                debug_assert!(
+                    !program.is_pure() || {
+                        let (rx, ry) = reference();
+                        vm::agrees(&x, &rx) && vm::agrees(&y, &ry)
+                    },
+                    "vm and tree walker disagree on a path function"
+                );
+                (x, y)
+            }
+            None => reference(),
+        };
+        Vector2::new(extract_numeric(x), extract_numeric(y))
     }
 
     fn entity_pos_as_expr(entity_position: Vector2<f64>) -> ExpressionType {
-        ExpressionType::Vector(vec![
-            ExpressionType::Float(entity_position[0]),
-            ExpressionType::Float(entity_position[1]),
+        ExpressionKind::Vector(vec![
+            ExpressionKind::Float(entity_position[0]).into(),
+            ExpressionKind::Float(entity_position[1]).into(),
         ])
+        .into()
     }
 
-    // create spawn-time globals -- these will not be accurate for per frame movements
-    pub fn create_globals(entity_position: Vector2<f64>) -> Values {
-        let mut globals: Values = HashMap::new();
+    // globals seen by an entity's pattern on the current frame
+    //   rebuilt every time the pattern is resumed, so spawns use the state of the firing frame
+    pub fn create_globals(
+        environment: &ExecutionEnvironment,
+        targets: &TargetMap,
+        grid: &SpatialHash,
+        proximity_radius: f64,
+        fps: u16,
+    ) -> Values {
+        let position = environment.entity.position;
+        let mut globals = target::player_globals(targets, position);
+        // entities crowding the player and this entity, the entity itself not included
+        //   an entity at a NaN or infinite position isn't in its own count to take away
+        let player = target::player_state(targets, position);
+        let near_player = grid
+            .count_within_radius(player.position, proximity_radius)
+            .saturating_sub((player.distance <= proximity_radius) as usize);
+        let near_self = grid
+            .count_within_radius(position, proximity_radius)
+            .saturating_sub(1);
+        globals.insert(
+            "bullets_near_player".to_string(),
+            ExpressionKind::Int(near_player as i64).into(),
+        );
         globals.insert(
-            "towards_player".to_string(),
-            Interpreter::angle_towards_player(),
+            "bullets_near".to_string(),
+            ExpressionKind::Int(near_self as i64).into(),
         );
         globals.insert(
             "entity_position".to_string(),
-            Interpreter::entity_pos_as_expr(entity_position),
+            Interpreter::entity_pos_as_expr(environment.entity.position),
+        );
+        globals.insert(
+            "entity_rotation".to_string(),
+            ExpressionKind::Float(environment.entity.rotation.0 as f64).into(),
+        );
+        globals.insert(
+            "entity_velocity".to_string(),
+            Interpreter::entity_pos_as_expr(environment.entity.velocity),
+        );
+        // time the entity has been alive, in frames and seconds
+        globals.insert(
+            "elapsed".to_string(),
+            ExpressionKind::Int(environment.elapsed as i64).into(),
+        );
+        globals.insert(
+            "elapsed_seconds".to_string(),
+            ExpressionKind::Float(environment.elapsed as f64 / fps as f64).into(),
         );
         globals
     }
 
     pub fn step(&mut self) {
+        // builtins draw from the interpreter's generator for the length of the step, and
+        // expressions can call its functions
+        //   moved out so the rest of the interpreter can be borrowed while they're lent
+        let mut rng = std::mem::take(&mut self.rng);
+        let mut functions = std::mem::take(&mut self.functions);
+        {
+            let _rng = random::Lent::new(&mut rng);
+            let _functions = functions::Lent::new(&mut functions);
+            self.advance();
+        }
+        self.rng = rng;
+        self.functions = functions;
+        self.elapsed += 1;
+    }
+
+    fn advance(&mut self) {
         // collect all new emplacements per frame
-        let mut pooled_new_actions: Actions = Vec::new();
+        let mut pooled_new_actions: Vec<Option<Coroutine>> = Vec::new();
         let mut pooled_new_entities: Vec<ExecutionEnvironment> = Vec::new();
         let mut batched_deletions: Vec<usize> = Vec::new();
 
         // move current entity according to velocity rules
-        Interpreter::move_entities(&mut self.entities, self.fps);
+        Interpreter::move_entities(&mut self.entities, &self.targets, self.fps);
+        // wrap and bounce what left the playfield, the rest of the escapees are swept below
+        let escaped = match &self.playfield {
+            Some(playfield) => bounds::confine(&mut self.entities, playfield),
+            None => Vec::new(),
+        };
+        self.grid.rebuild(&self.entities);
 
         // step behavior of each adding new ents to pool: spawns, subpatterns
         for i in 0..self.entities.len() {
-            let mut removed_callback_indices: Vec<usize> = Vec::new();
-            // lifetime outlives, remove and don't check actions
-            if self.entities[i].duration <= self.entities[i].elapsed {
+            // lifetime outlives or it's off the playfield, remove and don't resume its pattern
+            if self.entities[i].duration <= self.entities[i].elapsed
+                || escaped.binary_search(&i).is_ok()
+            {
                 batched_deletions.push(i);
                 continue;
             }
-            match &mut self.actions[i] {
-                Some(actions) => {
-                    for callback_index in 0..actions.len() {
-                        let callback = &actions[callback_index];
-                        if callback.frame <= self.entities[i].elapsed {
-                            removed_callback_indices.push(callback_index);
-                            let result = (*callback.func.0)(
-                                &mut self.entities[i],
-                                &self.paths,
-                                &self.patterns,
-                                &self.prefabs,
-                                &self.bullets,
-                            );
-                            match result {
-                                CallbackResult::AddEntities(ents) => {
-                                    for ent in &ents {
-                                        let globals = Interpreter::create_globals(
-                                            self.entities[i].entity.position,
-                                        );
-                                        pooled_new_entities.push(ExecutionEnvironment::new(ent));
-                                        let new_actions = ent.compile_behavior(
-                                            &self.paths,
-                                            &self.patterns,
-                                            &self.prefabs,
-                                            &self.bullets,
-                                            globals,
-                                            self.fps,
-                                        );
-                                        pooled_new_actions.push(new_actions);
-                                    }
-                                }
-                                CallbackResult::Delete => batched_deletions.push(i),
-                                CallbackResult::Mutate => {}
-                            }
-                        }
+            if let Some(coroutine) = &mut self.actions[i] {
+                let globals = Interpreter::create_globals(
+                    &self.entities[i],
+                    &self.targets,
+                    &self.grid,
+                    self.proximity_radius,
+                    self.fps,
+                );
+                let spawned = coroutine.resume(
+                    &self.patterns,
+                    &self.paths,
+                    &self.bullets,
+                    &globals,
+                    self.fps,
+                );
+                if coroutine.finished {
+                    self.actions[i] = None;
+                    // emitters have nothing left to do once their pattern is done
+                    if self.entities[i].entity.is_emitter() {
+                        batched_deletions.push(i);
                     }
-                    // remove singular entity's spent callbacks and advance its lifetime
-                    swap_remove_all(actions, &removed_callback_indices);
-                    self.entities[i].elapsed += 1;
                 }
-                None => {}
+                for ent in &spawned {
+                    pooled_new_entities.push(ExecutionEnvironment::new(ent));
+                    pooled_new_actions.push(ent.compile_behavior(&self.patterns, self.fps));
+                }
             }
+            // advance its lifetime
+            self.entities[i].elapsed += 1;
         }
-        // sweep the marked dead entities -- a dead entity can have no callbacks
+        // sweep the marked dead entities -- a dead entity can have no running pattern
         swap_remove_all(&mut self.entities, &batched_deletions);
         swap_remove_all(&mut self.actions, &batched_deletions);
         // add pool to current
         self.entities.append(&mut pooled_new_entities);
         self.actions.append(&mut pooled_new_actions);
 
-        self.elapsed += 1;
+        // test everything where it ended up this frame against the targets
+        self.grid.rebuild(&self.entities);
+        self.hits = collision::detect(&self.entities, &self.targets, &self.grid, &self.collision);
+        if self.collision.despawn_on_hit && !self.hits.is_empty() {
+            let mut hit_entities: Vec<usize> = self.hits.iter().filter_map(|h| h.entity).collect();
+            hit_entities.dedup();
+            // the indices would point at whatever the removals move into place
+            for hit in &mut self.hits {
+                if let Some(i) = hit.entity.take() {
+                    hit.despawned = Some(self.entities[i].entity.clone());
+                }
+            }
+            swap_remove_all(&mut self.entities, &hit_entities);
+            swap_remove_all(&mut self.actions, &hit_entities);
+            self.grid.rebuild(&self.entities);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::Parser;
+
+    const SPRAY: &str = "
+        fn jitter(x) = x + rand_range(-5, 5);
+
+        pattern spray() = {
+            iteration_type = loop;
+            actions = {
+                spawn {
+                    rotation = rand_angle();
+                    speed = jitter(100);
+                }
+                wait 2 frames;
+            };
+        }
+    ";
+
+    fn spraying(seed: u64) -> Interpreter {
+        let head = Parser::parse_from_string(SPRAY.to_string()).unwrap();
+        let mut interpreter = Interpreter::from_parse_result(head).unwrap();
+        interpreter.set_seed(seed);
+        let mut emitter = Entity::new();
+        emitter.behavior = Behavior::Pattern("spray".to_string());
+        interpreter.spawn_direct(&emitter);
+        interpreter
+    }
+
+    fn trace(interpreter: &Interpreter) -> Vec<(f64, f64, f32)> {
+        interpreter
+            .entities
+            .iter()
+            .map(|e| {
+                (
+                    e.entity.position.x,
+                    e.entity.position.y,
+                    e.entity.rotation.0,
+                )
+            })
+            .collect()
+    }
+
+    fn run(interpreter: &mut Interpreter, steps: usize) -> Vec<Vec<(f64, f64, f32)>> {
+        (0..steps)
+            .map(|_| {
+                interpreter.step();
+                trace(interpreter)
+            })
+            .collect()
+    }
+
+    // stepped in turn on one thread, each has to get its own generator back every step
+    #[test]
+    fn same_seed_same_entities() {
+        let alone = run(&mut spraying(7), 60);
+        assert_eq!(alone.last().map(|entities| entities.len()), Some(31));
+        let mut first = spraying(7);
+        let mut other = spraying(8);
+        let mut traces = (Vec::new(), Vec::new());
+        for _ in 0..60 {
+            traces.0.extend(run(&mut first, 1));
+            traces.1.extend(run(&mut other, 1));
+        }
+        assert_eq!(traces.0, alone);
+        assert_ne!(traces.1, alone);
     }
 }
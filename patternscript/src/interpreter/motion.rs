@@ -0,0 +1,77 @@
+use super::bytecode::Program;
+use super::primitive::Primitive;
+use super::vm;
+use cgmath::{InnerSpace, Vector2};
+
+#[derive(Clone, Debug)]
+pub enum Acceleration {
+    // speeding up along the heading, or slowing down when negative
+    Along(f64),
+    // a constant push in one direction such as gravity, which also bends the heading
+    Toward(Vector2<f64>),
+}
+
+// how an entity's speed and heading change on their own from frame to frame, per second
+//   paths decide their own motion, so entities following one ignore all of this
+#[derive(Clone, Debug, Default)]
+pub struct Motion {
+    pub acceleration: Option<Acceleration>,
+    // degrees the heading turns, curving the entity's path
+    pub angular_velocity: f64,
+    pub max_speed: Option<f64>,
+    pub min_speed: Option<f64>,
+    // fraction of its speed the entity loses
+    pub friction: f64,
+    // speed as an expression of t, the frames the entity has been alive, instead of the
+    // speed it had the frame before; compiled on spawn against the spawning scope
+    pub speed_over_time: Option<Program>,
+}
+
+impl Motion {
+    // nothing to integrate, the entity keeps the velocity it has
+    pub fn is_inert(&self) -> bool {
+        self.acceleration.is_none()
+            && self.angular_velocity == 0.0
+            && self.max_speed.is_none()
+            && self.min_speed.is_none()
+            && self.friction == 0.0
+            && self.speed_over_time.is_none()
+    }
+
+    // speed and heading in degrees after dt more seconds, at elapsed frames
+    //   a speed curve that doesn't evaluate to a number leaves the speed as it was
+    pub fn step(&self, speed: f64, heading: f64, elapsed: u32, dt: f64) -> (f64, f64) {
+        let mut heading = heading + self.angular_velocity * dt;
+        let mut speed = match &self.speed_over_time {
+            Some(curve) => speed_at(curve, elapsed).unwrap_or(speed),
+            None => match self.acceleration {
+                Some(Acceleration::Along(a)) => speed + a * dt,
+                _ => speed,
+            },
+        };
+        speed *= (1.0 - self.friction * dt).max(0.0);
+        if let Some(Acceleration::Toward(a)) = self.acceleration {
+            let (sin, cos) = heading.to_radians().sin_cos();
+            let velocity = Vector2::new(speed * cos, speed * sin) + a * dt;
+            speed = velocity.magnitude();
+            if speed > 0.0 {
+                heading = velocity.y.atan2(velocity.x).to_degrees();
+            }
+        }
+        if let Some(max) = self.max_speed {
+            speed = speed.min(max);
+        }
+        if let Some(min) = self.min_speed {
+            speed = speed.max(min);
+        }
+        (speed, heading)
+    }
+}
+
+fn speed_at(curve: &Program, elapsed: u32) -> Option<f64> {
+    match vm::execute(curve, &[Primitive::I64(elapsed as i64)]) {
+        Ok(Primitive::I64(i)) => Some(i as f64),
+        Ok(Primitive::F64(f)) => Some(f),
+        _ => None,
+    }
+}
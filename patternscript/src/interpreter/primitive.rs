@@ -1,7 +1,9 @@
 use super::error::RuntimeError;
+use crate::parser::parser::{ExpressionKind, ExpressionType};
+use crate::parser::types::Span;
 use anyhow::Result;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Primitive {
     I64(i64),
     F64(f64),
@@ -12,6 +14,69 @@ pub enum Primitive {
     Bool(bool),
 }
 
+impl Primitive {
+    // the literal expression that evaluates back to this value
+    //   booleans have no literal syntax, so they can't be folded back into the tree
+    pub fn into_expression(self, span: Span) -> Option<ExpressionType> {
+        let literal = |kind| ExpressionType::new(kind, span);
+        let kind = match self {
+            Primitive::I64(i) => ExpressionKind::Int(i),
+            Primitive::F64(f) => ExpressionKind::Float(f),
+            Primitive::String(s) => ExpressionKind::String(s),
+            Primitive::IntVec(v) => ExpressionKind::Vector(
+                v.into_iter()
+                    .map(|i| literal(ExpressionKind::Int(i)))
+                    .collect(),
+            ),
+            Primitive::FloatVec(v) => ExpressionKind::Vector(
+                v.into_iter()
+                    .map(|f| literal(ExpressionKind::Float(f)))
+                    .collect(),
+            ),
+            Primitive::StrVec(v) => ExpressionKind::Vector(
+                v.into_iter()
+                    .map(|s| literal(ExpressionKind::String(s)))
+                    .collect(),
+            ),
+            Primitive::Bool(_) => return None,
+        };
+        Some(literal(kind))
+    }
+}
+
+// build a vector from its evaluated elements
+//   all ints stay an int vector, a mix of ints and floats is promoted to a float vector
+pub fn primitive_vector(elements: Vec<Primitive>, span: Span) -> Result<Primitive> {
+    use Primitive::*;
+    match elements.first() {
+        Some(I64(_)) if elements.iter().all(|e| matches!(e, I64(_))) => Ok(IntVec(
+            elements
+                .into_iter()
+                .filter_map(|e| if let I64(i) = e { Some(i) } else { None })
+                .collect(),
+        )),
+        Some(I64(_) | F64(_)) if elements.iter().all(|e| matches!(e, I64(_) | F64(_))) => {
+            Ok(FloatVec(
+                elements
+                    .into_iter()
+                    .filter_map(|e| match e {
+                        I64(i) => Some(i as f64),
+                        F64(f) => Some(f),
+                        _ => None,
+                    })
+                    .collect(),
+            ))
+        }
+        Some(String(_)) if elements.iter().all(|e| matches!(e, String(_))) => Ok(StrVec(
+            elements
+                .into_iter()
+                .filter_map(|e| if let String(s) = e { Some(s) } else { None })
+                .collect(),
+        )),
+        _ => Err(RuntimeError::VecTypeError(span).into()),
+    }
+}
+
 #[derive(Debug)]
 pub enum PrimitiveVecOp {
     Add,
@@ -24,9 +89,17 @@ pub fn primitive_vec_arithmetic(
     op: PrimitiveVecOp,
     lhs: Primitive,
     rhs: Primitive,
+    span: Span,
 ) -> Result<Primitive> {
     use Primitive::*;
     use PrimitiveVecOp::*;
+    // element by element, zip would quietly drop the longer vector's tail
+    let lengths = (vector_length(&lhs), vector_length(&rhs));
+    if let (Some(l), Some(r)) = lengths {
+        if l != r {
+            return Err(RuntimeError::VecLengthMismatch(l, r, span).into());
+        }
+    }
     fn zipmap_vec<A, B, C>(func: fn((&A, &B)) -> C, lhs: &[A], rhs: &[B]) -> Vec<C> {
         lhs.iter().zip(rhs.iter()).map(func).collect()
     }
@@ -85,3 +158,45 @@ pub fn primitive_vec_arithmetic(
         _ => (Err(RuntimeError::VecArithTypeError.into())),
     }
 }
+
+fn vector_length(p: &Primitive) -> Option<usize> {
+    match p {
+        Primitive::IntVec(v) => Some(v.len()),
+        Primitive::FloatVec(v) => Some(v.len()),
+        Primitive::StrVec(v) => Some(v.len()),
+        _ => None,
+    }
+}
+
+// a numeric vector as floats, None for anything else
+pub fn float_vector(p: &Primitive) -> Option<Vec<f64>> {
+    match p {
+        Primitive::IntVec(v) => Some(v.iter().map(|i| *i as f64).collect()),
+        Primitive::FloatVec(v) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+// every element of a vector scaled by a number, scalar * vector, vector * scalar and
+// vector / scalar; like vector * vector the result is always floats
+pub fn primitive_vec_scale(
+    op: PrimitiveVecOp,
+    vector: Primitive,
+    scalar: Primitive,
+) -> Result<Primitive> {
+    let scalar = match scalar {
+        Primitive::I64(i) => i as f64,
+        Primitive::F64(f) => f,
+        _ => return Err(RuntimeError::VecArithTypeError.into()),
+    };
+    let vector = float_vector(&vector).ok_or(RuntimeError::VecArithTypeError)?;
+    match op {
+        PrimitiveVecOp::Mul => Ok(Primitive::FloatVec(
+            vector.into_iter().map(|e| e * scalar).collect(),
+        )),
+        PrimitiveVecOp::Div => Ok(Primitive::FloatVec(
+            vector.into_iter().map(|e| e / scalar).collect(),
+        )),
+        _ => Err(RuntimeError::VecArithTypeError.into()),
+    }
+}
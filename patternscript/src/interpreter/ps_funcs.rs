@@ -1,78 +1,582 @@
+use super::curves;
 use super::error::*;
 use super::evaluate::Evaluate;
+use super::functions;
 use super::primitive::*;
+use super::random;
 use crate::parser::parser::{ExpressionType, Values};
+use crate::parser::types::Span;
 use anyhow::Result;
 use cgmath::{Angle, Deg};
+use std::f64::consts::{PI, TAU};
 
 pub fn dispatch_func(
-    fn_name: String,
+    fn_name: &str,
     values: &Values,
-    arg: Box<ExpressionType>,
+    args: Vec<ExpressionType>,
 ) -> Result<Primitive> {
-    match fn_name.as_str() {
-        "sqrt" => sqrt(values, arg),
-        "sin" => sin(values, arg),
-        "cos" => cos(values, arg),
-        "tan" => tan(values, arg),
-        "x" => access_x(values, arg),
-        "y" => access_y(values, arg),
-        _ => (unimplemented!()),
+    let args = args
+        .into_iter()
+        .map(|arg| arg.eval(values))
+        .collect::<Result<Vec<Primitive>>>()?;
+    call_builtin(fn_name, args)
+}
+
+// every name call_builtin knows
+pub const BUILTINS: [&str; 58] = [
+    "sqrt",
+    "sin",
+    "cos",
+    "tan",
+    "asin",
+    "acos",
+    "atan2",
+    "exp",
+    "ln",
+    "abs",
+    "sign",
+    "floor",
+    "ceil",
+    "round",
+    "min",
+    "max",
+    "clamp",
+    "mod",
+    "lerp",
+    "smoothstep",
+    "x",
+    "y",
+    "length",
+    "normalize",
+    "dot",
+    "cross2",
+    "rotate",
+    "angle_of",
+    "from_angle",
+    "distance",
+    "ease_in_quad",
+    "ease_out_quad",
+    "ease_in_out_quad",
+    "ease_in_cubic",
+    "ease_out_cubic",
+    "ease_in_out_cubic",
+    "ease_in_sine",
+    "ease_out_sine",
+    "ease_in_out_sine",
+    "ease_in_expo",
+    "ease_out_expo",
+    "ease_in_out_expo",
+    "ease_in_back",
+    "ease_out_back",
+    "ease_in_out_back",
+    "ease_in_elastic",
+    "ease_out_elastic",
+    "ease_in_out_elastic",
+    "bezier",
+    "spline",
+    "ping_pong",
+    "repeat",
+    "rand",
+    "rand_range",
+    "rand_int",
+    "rand_angle",
+    "rand_choice",
+    "rand_in_circle",
+];
+
+// builtins that give a different result each call, which can't be folded or evaluated twice
+pub const IMPURE_BUILTINS: [&str; 6] = [
+    "rand",
+    "rand_range",
+    "rand_int",
+    "rand_angle",
+    "rand_choice",
+    "rand_in_circle",
+];
+
+// names bound everywhere unless something in scope shadows them
+pub const CONSTANTS: [(&str, f64); 2] = [("pi", PI), ("tau", TAU)];
+
+pub fn is_pure(fn_name: &str) -> bool {
+    !IMPURE_BUILTINS.contains(&fn_name) && functions::is_pure(fn_name)
+}
+
+pub fn constant(name: &str) -> Option<f64> {
+    CONSTANTS
+        .iter()
+        .find(|(constant, _)| *constant == name)
+        .map(|(_, value)| *value)
+}
+
+// how many arguments a builtin takes, as (fewest, most); None if it isn't one
+//   x, y, length, normalize, angle_of and rand_choice take one vector, but its elements can also be passed one by one
+pub fn arity(fn_name: &str) -> Option<(usize, usize)> {
+    let arity = match fn_name {
+        "rand" | "rand_angle" => (0, 0),
+        "sqrt" | "sin" | "cos" | "tan" | "asin" | "acos" | "exp" | "ln" | "abs" | "sign"
+        | "floor" | "ceil" | "round" | "rand_in_circle" => (1, 1),
+        "atan2" | "mod" | "rand_range" | "rand_int" | "dot" | "cross2" | "rotate"
+        | "from_angle" | "distance" | "ping_pong" | "repeat" => (2, 2),
+        "clamp" | "lerp" | "smoothstep" => (3, 3),
+        "bezier" => (5, 5),
+        // spline(points, u) or spline(p0, p1, .., u)
+        "spline" => (2, usize::MAX),
+        easing if curves::ease(easing, 0.0).is_some() => (1, 1),
+        "min" | "max" | "x" | "y" | "length" | "normalize" | "angle_of" | "rand_choice" => {
+            (1, usize::MAX)
+        }
+        _ => return None,
+    };
+    Some(arity)
+}
+
+// "2", "1 or more", for errors
+pub fn describe_arity((fewest, most): (usize, usize)) -> String {
+    if fewest == most {
+        fewest.to_string()
+    } else if most == usize::MAX {
+        format!("{} or more", fewest)
+    } else {
+        format!("{} to {}", fewest, most)
     }
 }
 
-// todo: make this better and add array indexing to language
+// builtins and functions defined with fn on already evaluated arguments, shared by the
+// tree walker and the vm; a function can't replace a builtin of the same name
+pub fn call_builtin(fn_name: &str, args: Vec<Primitive>) -> Result<Primitive> {
+    let arity = match arity(fn_name) {
+        Some(arity) => arity,
+        None => return functions::call(fn_name, args),
+    };
+    if args.len() < arity.0 || args.len() > arity.1 {
+        return Err(RuntimeError::BuiltinArity(
+            fn_name.to_string(),
+            describe_arity(arity),
+            args.len(),
+        )
+        .into());
+    }
+    let mut args = args.into_iter();
+    match fn_name {
+        "sqrt" => Ok(Primitive::F64(float(fn_name, next(&mut args))?.sqrt())),
+        "sin" => Ok(Primitive::F64(Deg(float(fn_name, next(&mut args))?).sin())),
+        "cos" => Ok(Primitive::F64(Deg(float(fn_name, next(&mut args))?).cos())),
+        "tan" => Ok(Primitive::F64(Deg(float(fn_name, next(&mut args))?).tan())),
+        "asin" => Ok(Primitive::F64(
+            Deg::asin(float(fn_name, next(&mut args))?).0,
+        )),
+        "acos" => Ok(Primitive::F64(
+            Deg::acos(float(fn_name, next(&mut args))?).0,
+        )),
+        "atan2" => {
+            let y = float(fn_name, next(&mut args))?;
+            let x = float(fn_name, next(&mut args))?;
+            Ok(Primitive::F64(Deg::atan2(y, x).0))
+        }
+        "exp" => Ok(Primitive::F64(float(fn_name, next(&mut args))?.exp())),
+        "ln" => Ok(Primitive::F64(float(fn_name, next(&mut args))?.ln())),
+        "abs" => abs(next(&mut args)),
+        "sign" => sign(next(&mut args)),
+        "floor" => rounded(fn_name, next(&mut args), f64::floor),
+        "ceil" => rounded(fn_name, next(&mut args), f64::ceil),
+        "round" => rounded(fn_name, next(&mut args), f64::round),
+        "min" => extremum(fn_name, spread(args.collect()), |a, b| b < a),
+        "max" => extremum(fn_name, spread(args.collect()), |a, b| b > a),
+        "clamp" => clamp(args.collect()),
+        "mod" => modulo(next(&mut args), next(&mut args)),
+        "lerp" => lerp(args.collect()),
+        "smoothstep" => smoothstep(args.collect()),
+        "x" => access_x(gather(args.collect())?),
+        "y" => access_y(gather(args.collect())?),
+        "length" => Ok(Primitive::F64(magnitude(&vector(
+            fn_name,
+            gather(args.collect())?,
+        )?))),
+        "normalize" => normalize(gather(args.collect())?),
+        "dot" => {
+            let (a, b) = vector_pair(fn_name, next(&mut args), next(&mut args))?;
+            Ok(Primitive::F64(
+                a.iter().zip(b.iter()).map(|(a, b)| a * b).sum(),
+            ))
+        }
+        "cross2" => {
+            let (a, b) = (
+                plane(fn_name, next(&mut args))?,
+                plane(fn_name, next(&mut args))?,
+            );
+            Ok(Primitive::F64(a.0 * b.1 - a.1 * b.0))
+        }
+        "rotate" => rotate(next(&mut args), next(&mut args)),
+        "angle_of" => {
+            let (x, y) = plane(fn_name, gather(args.collect())?)?;
+            Ok(Primitive::F64(Deg::atan2(y, x).0))
+        }
+        "from_angle" => {
+            let angle = Deg(float(fn_name, next(&mut args))?);
+            let magnitude = float(fn_name, next(&mut args))?;
+            Ok(Primitive::FloatVec(vec![
+                magnitude * angle.cos(),
+                magnitude * angle.sin(),
+            ]))
+        }
+        "distance" => {
+            let (a, b) = vector_pair(fn_name, next(&mut args), next(&mut args))?;
+            let difference: Vec<f64> = a.iter().zip(b.iter()).map(|(a, b)| a - b).collect();
+            Ok(Primitive::F64(magnitude(&difference)))
+        }
+        "bezier" => bezier(args.collect()),
+        "spline" => spline(args.collect()),
+        "ping_pong" => ping_pong(next(&mut args), next(&mut args)),
+        "repeat" => repeat(next(&mut args), next(&mut args)),
+        "rand" => rand(),
+        "rand_range" => rand_range(next(&mut args), next(&mut args)),
+        "rand_int" => rand_int(next(&mut args), next(&mut args)),
+        "rand_angle" => rand_angle(),
+        "rand_choice" => rand_choice(gather(args.collect())?),
+        "rand_in_circle" => rand_in_circle(next(&mut args)),
+        easing => match curves::ease(easing, float(fn_name, next(&mut args))?) {
+            Some(eased) => Ok(Primitive::F64(eased)),
+            None => Err(RuntimeError::UnknownFunction(fn_name.to_string()).into()),
+        },
+    }
+}
 
-fn sqrt(values: &Values, arg: Box<ExpressionType>) -> Result<Primitive> {
-    use Primitive::*;
-    match arg.eval(values)? {
-        I64(i) => Ok(F64((i as f64).sqrt())),
-        F64(f) => Ok(F64(f.sqrt())),
-        _ => Err(RuntimeError::Generic.into()),
+fn next(args: &mut impl Iterator<Item = Primitive>) -> Primitive {
+    args.next().expect("arity was checked")
+}
+
+// numeric arguments, all ints or all promoted to floats as soon as one of them is a float
+enum Numbers {
+    Ints(Vec<i64>),
+    Floats(Vec<f64>),
+}
+
+fn numbers(fn_name: &str, args: Vec<Primitive>) -> Result<Numbers> {
+    if args.iter().any(|arg| matches!(arg, Primitive::F64(_))) {
+        return Ok(Numbers::Floats(floats(fn_name, args)?));
     }
+    args.into_iter()
+        .map(|arg| match arg {
+            Primitive::I64(i) => Ok(i),
+            _ => Err(RuntimeError::BuiltinArgument(fn_name.to_string(), "numbers").into()),
+        })
+        .collect::<Result<Vec<i64>>>()
+        .map(Numbers::Ints)
 }
 
-fn sin(values: &Values, arg: Box<ExpressionType>) -> Result<Primitive> {
-    use Primitive::*;
-    match arg.eval(values)? {
-        I64(i) => Ok(F64(Deg(i as f64).sin())),
-        F64(f) => Ok(F64(Deg(f).sin())),
-        _ => Err(RuntimeError::Generic.into()),
+fn float(fn_name: &str, arg: Primitive) -> Result<f64> {
+    match arg {
+        Primitive::I64(i) => Ok(i as f64),
+        Primitive::F64(f) => Ok(f),
+        _ => Err(RuntimeError::BuiltinArgument(fn_name.to_string(), "a number").into()),
     }
 }
 
-fn cos(values: &Values, arg: Box<ExpressionType>) -> Result<Primitive> {
-    use Primitive::*;
-    match arg.eval(values)? {
-        I64(i) => Ok(F64(Deg(i as f64).cos())),
-        F64(f) => Ok(F64(Deg(f).cos())),
-        _ => Err(RuntimeError::Generic.into()),
+fn floats(fn_name: &str, args: Vec<Primitive>) -> Result<Vec<f64>> {
+    args.into_iter().map(|arg| float(fn_name, arg)).collect()
+}
+
+// f(v) on a vector v is the same as f(v[0], v[1], ..)
+fn spread(args: Vec<Primitive>) -> Vec<Primitive> {
+    match args.as_slice() {
+        [Primitive::IntVec(v)] => v.iter().map(|i| Primitive::I64(*i)).collect(),
+        [Primitive::FloatVec(v)] => v.iter().map(|f| Primitive::F64(*f)).collect(),
+        _ => args,
     }
 }
 
-fn tan(values: &Values, arg: Box<ExpressionType>) -> Result<Primitive> {
-    use Primitive::*;
-    match arg.eval(values)? {
-        I64(i) => Ok(F64(Deg(i as f64).tan())),
-        F64(f) => Ok(F64(Deg(f).tan())),
-        _ => Err(RuntimeError::Generic.into()),
+// and the other way, f(a, b, ..) is f((a, b, ..)) for builtins taking one vector
+fn gather(args: Vec<Primitive>) -> Result<Primitive> {
+    match args.len() {
+        1 => Ok(args.into_iter().next().unwrap()),
+        _ => primitive_vector(args, Span::default()),
     }
 }
 
-fn access_x(values: &Values, arg: Box<ExpressionType>) -> Result<Primitive> {
-    match arg.eval(values)? {
+fn abs(arg: Primitive) -> Result<Primitive> {
+    match arg {
+        Primitive::I64(i) => Ok(Primitive::I64(i.abs())),
+        Primitive::F64(f) => Ok(Primitive::F64(f.abs())),
+        _ => Err(RuntimeError::BuiltinArgument("abs".to_string(), "a number").into()),
+    }
+}
+
+// -1, 0 or 1, in the argument's type
+fn sign(arg: Primitive) -> Result<Primitive> {
+    match arg {
+        Primitive::I64(i) => Ok(Primitive::I64(i.signum())),
+        // f64::signum gives 1 for 0
+        Primitive::F64(f) => Ok(Primitive::F64(if f == 0.0 { 0.0 } else { f.signum() })),
+        _ => Err(RuntimeError::BuiltinArgument("sign".to_string(), "a number").into()),
+    }
+}
+
+// floor, ceil and round give ints, ints are already whole
+fn rounded(fn_name: &str, arg: Primitive, round: fn(f64) -> f64) -> Result<Primitive> {
+    match arg {
+        Primitive::I64(i) => Ok(Primitive::I64(i)),
+        Primitive::F64(f) => Ok(Primitive::I64(round(f) as i64)),
+        _ => Err(RuntimeError::BuiltinArgument(fn_name.to_string(), "a number").into()),
+    }
+}
+
+// min and max, replace says whether b should replace the best a so far
+fn extremum(
+    fn_name: &str,
+    args: Vec<Primitive>,
+    replace: fn(f64, f64) -> bool,
+) -> Result<Primitive> {
+    match numbers(fn_name, args)? {
+        Numbers::Ints(ints) => Ok(Primitive::I64(
+            ints.into_iter()
+                .reduce(|a, b| if replace(a as f64, b as f64) { b } else { a })
+                .ok_or_else(|| RuntimeError::BuiltinArgument(fn_name.to_string(), "a number"))?,
+        )),
+        Numbers::Floats(floats) => Ok(Primitive::F64(
+            floats
+                .into_iter()
+                .reduce(|a, b| if replace(a, b) { b } else { a })
+                .ok_or_else(|| RuntimeError::BuiltinArgument(fn_name.to_string(), "a number"))?,
+        )),
+    }
+}
+
+// clamp(v, low, high)
+fn clamp(args: Vec<Primitive>) -> Result<Primitive> {
+    match numbers("clamp", args)? {
+        Numbers::Ints(n) => Ok(Primitive::I64(n[0].max(n[1]).min(n[2]))),
+        Numbers::Floats(n) => Ok(Primitive::F64(n[0].max(n[1]).min(n[2]))),
+    }
+}
+
+// the remainder, never negative, so mod(-30, 360) is 330
+fn modulo(lhs: Primitive, rhs: Primitive) -> Result<Primitive> {
+    match numbers("mod", vec![lhs, rhs])? {
+        Numbers::Ints(n) if n[1] == 0 => {
+            Err(RuntimeError::BuiltinArgument("mod".to_string(), "a divisor other than 0").into())
+        }
+        Numbers::Ints(n) => Ok(Primitive::I64(n[0].rem_euclid(n[1]))),
+        Numbers::Floats(n) => Ok(Primitive::F64(n[0].rem_euclid(n[1]))),
+    }
+}
+
+// lerp(a, b, u), a at 0 and b at 1
+fn lerp(args: Vec<Primitive>) -> Result<Primitive> {
+    let n = floats("lerp", args)?;
+    Ok(Primitive::F64(n[0] + (n[1] - n[0]) * n[2]))
+}
+
+// smoothstep(edge0, edge1, x), 0 before edge0 easing up to 1 after edge1
+fn smoothstep(args: Vec<Primitive>) -> Result<Primitive> {
+    let n = floats("smoothstep", args)?;
+    let u = ((n[2] - n[0]) / (n[1] - n[0])).clamp(0.0, 1.0);
+    Ok(Primitive::F64(u * u * (3.0 - 2.0 * u)))
+}
+
+// control points of a curve, all numbers or all vectors of the same length
+//   numbers are treated as vectors of 1 and given back as a number
+fn control_points(fn_name: &str, points: Vec<Primitive>) -> Result<(Vec<Vec<f64>>, bool)> {
+    let scalar = points
+        .iter()
+        .all(|p| matches!(p, Primitive::I64(_) | Primitive::F64(_)));
+    let points = points
+        .into_iter()
+        .map(|p| match p {
+            Primitive::I64(_) | Primitive::F64(_) if scalar => Ok(vec![float(fn_name, p)?]),
+            p => vector(fn_name, p),
+        })
+        .collect::<Result<Vec<Vec<f64>>>>()?;
+    if let Some(mismatch) = points.iter().find(|p| p.len() != points[0].len()) {
+        return Err(RuntimeError::VecLengthMismatch(
+            points[0].len(),
+            mismatch.len(),
+            Span::default(),
+        )
+        .into());
+    }
+    Ok((points, scalar))
+}
+
+fn curve_point(point: Vec<f64>, scalar: bool) -> Primitive {
+    if scalar {
+        Primitive::F64(point[0])
+    } else {
+        Primitive::FloatVec(point)
+    }
+}
+
+// bezier(p0, p1, p2, p3, u)
+fn bezier(mut args: Vec<Primitive>) -> Result<Primitive> {
+    let u = float("bezier", args.pop().expect("arity was checked"))?;
+    let (points, scalar) = control_points("bezier", args)?;
+    let point = curves::bezier([&points[0], &points[1], &points[2], &points[3]], u);
+    Ok(curve_point(point, scalar))
+}
+
+// spline(points, u) through a vector of numbers, or spline(p0, p1, .., u) through each point
+fn spline(mut args: Vec<Primitive>) -> Result<Primitive> {
+    let u = float("spline", args.pop().expect("arity was checked"))?;
+    let (points, scalar) = control_points("spline", spread(args))?;
+    Ok(curve_point(curves::catmull_rom(&points, u), scalar))
+}
+
+// 0 up to length and back down, ints stay ints
+fn ping_pong(t: Primitive, length: Primitive) -> Result<Primitive> {
+    match numbers("ping_pong", vec![t, length])? {
+        Numbers::Ints(n) if n[1] == 0 => Err(RuntimeError::BuiltinArgument(
+            "ping_pong".to_string(),
+            "a length other than 0",
+        )
+        .into()),
+        Numbers::Ints(n) => Ok(Primitive::I64(
+            n[1] - (n[0].rem_euclid(2 * n[1]) - n[1]).abs(),
+        )),
+        Numbers::Floats(n) => Ok(Primitive::F64(curves::ping_pong(n[0], n[1]))),
+    }
+}
+
+// 0 up to length, then from 0 again
+fn repeat(t: Primitive, length: Primitive) -> Result<Primitive> {
+    match numbers("repeat", vec![t, length])? {
+        Numbers::Ints(n) if n[1] == 0 => {
+            Err(RuntimeError::BuiltinArgument("repeat".to_string(), "a length other than 0").into())
+        }
+        Numbers::Ints(n) => Ok(Primitive::I64(n[0].rem_euclid(n[1]))),
+        Numbers::Floats(n) => Ok(Primitive::F64(n[0].rem_euclid(n[1]))),
+    }
+}
+
+// todo: make this better and add array indexing to language
+
+fn vector(fn_name: &str, arg: Primitive) -> Result<Vec<f64>> {
+    float_vector(&arg)
+        .ok_or_else(|| RuntimeError::BuiltinArgument(fn_name.to_string(), "a vector").into())
+}
+
+// two vectors of the same length
+fn vector_pair(fn_name: &str, a: Primitive, b: Primitive) -> Result<(Vec<f64>, Vec<f64>)> {
+    let (a, b) = (vector(fn_name, a)?, vector(fn_name, b)?);
+    if a.len() != b.len() {
+        return Err(RuntimeError::VecLengthMismatch(a.len(), b.len(), Span::default()).into());
+    }
+    Ok((a, b))
+}
+
+// a 2d vector as x, y
+fn plane(fn_name: &str, arg: Primitive) -> Result<(f64, f64)> {
+    match vector(fn_name, arg)?.as_slice() {
+        [x, y] => Ok((*x, *y)),
+        _ => Err(RuntimeError::BuiltinArgument(fn_name.to_string(), "a vector of 2").into()),
+    }
+}
+
+fn magnitude(v: &[f64]) -> f64 {
+    v.iter().map(|e| e * e).sum::<f64>().sqrt()
+}
+
+// the same direction with length 1, the zero vector stays zero
+fn normalize(arg: Primitive) -> Result<Primitive> {
+    let v = vector("normalize", arg)?;
+    let length = magnitude(&v);
+    if length == 0.0 {
+        return Ok(Primitive::FloatVec(v));
+    }
+    Ok(Primitive::FloatVec(
+        v.into_iter().map(|e| e / length).collect(),
+    ))
+}
+
+// turned by degrees in the same direction as rotation
+fn rotate(v: Primitive, angle: Primitive) -> Result<Primitive> {
+    let (x, y) = plane("rotate", v)?;
+    let (sin, cos) = Deg(float("rotate", angle)?).sin_cos();
+    Ok(Primitive::FloatVec(vec![
+        x * cos - y * sin,
+        x * sin + y * cos,
+    ]))
+}
+
+fn access_x(arg: Primitive) -> Result<Primitive> {
+    match arg {
         Primitive::IntVec(i) => Ok(Primitive::I64(i[0])),
         Primitive::FloatVec(f) => Ok(Primitive::F64(f[0])),
         Primitive::StrVec(s) => Ok(Primitive::String(s[0].clone())),
-        _ => Err(RuntimeError::Generic.into()),
+        _ => Err(RuntimeError::BuiltinArgument("x".to_string(), "a vector").into()),
+    }
+}
+
+fn access_y(arg: Primitive) -> Result<Primitive> {
+    match arg {
+        Primitive::IntVec(i) if i.len() > 1 => Ok(Primitive::I64(i[1])),
+        Primitive::FloatVec(f) if f.len() > 1 => Ok(Primitive::F64(f[1])),
+        Primitive::StrVec(s) if s.len() > 1 => Ok(Primitive::String(s[1].clone())),
+        _ => Err(RuntimeError::BuiltinArgument("y".to_string(), "a vector of 2 or more").into()),
+    }
+}
+
+// uniform in [0, 1)
+fn rand() -> Result<Primitive> {
+    Ok(Primitive::F64(random::with_active(|rng| rng.next_f64())))
+}
+
+// uniform in [a, b)
+fn rand_range(low: Primitive, high: Primitive) -> Result<Primitive> {
+    let (low, high) = (float("rand_range", low)?, float("rand_range", high)?);
+    Ok(Primitive::F64(random::with_active(|rng| {
+        rng.range_f64(low, high)
+    })))
+}
+
+// uniform in [a, b], both included
+fn rand_int(low: Primitive, high: Primitive) -> Result<Primitive> {
+    match (low, high) {
+        (Primitive::I64(low), Primitive::I64(high)) => {
+            Ok(Primitive::I64(random::with_active(|rng| {
+                rng.range_i64(low, high)
+            })))
+        }
+        _ => Err(RuntimeError::BuiltinArgument("rand_int".to_string(), "two ints").into()),
     }
 }
 
-fn access_y(values: &Values, arg: Box<ExpressionType>) -> Result<Primitive> {
-    match arg.eval(values)? {
-        Primitive::IntVec(i) => Ok(Primitive::I64(i[1])),
-        Primitive::FloatVec(f) => Ok(Primitive::F64(f[1])),
-        Primitive::StrVec(s) => Ok(Primitive::String(s[1].clone())),
-        _ => Err(RuntimeError::Generic.into()),
+// degrees in [0, 360)
+fn rand_angle() -> Result<Primitive> {
+    Ok(Primitive::F64(random::with_active(|rng| {
+        rng.range_f64(0.0, 360.0)
+    })))
+}
+
+fn rand_choice(arg: Primitive) -> Result<Primitive> {
+    use Primitive::*;
+    let length = match &arg {
+        IntVec(i) => i.len(),
+        FloatVec(f) => f.len(),
+        StrVec(s) => s.len(),
+        _ => 0,
+    };
+    if length == 0 {
+        return Err(RuntimeError::BuiltinArgument(
+            "rand_choice".to_string(),
+            "a vector with at least one element",
+        )
+        .into());
     }
+    let i = random::with_active(|rng| rng.range_i64(0, length as i64 - 1)) as usize;
+    match arg {
+        IntVec(v) => Ok(I64(v[i])),
+        FloatVec(v) => Ok(F64(v[i])),
+        StrVec(mut v) => Ok(String(v.swap_remove(i))),
+        _ => unreachable!(),
+    }
+}
+
+// a point uniformly distributed over the disc of radius r around the origin
+fn rand_in_circle(arg: Primitive) -> Result<Primitive> {
+    let radius = float("rand_in_circle", arg)?;
+    let (angle, distance) = random::with_active(|rng| {
+        // sqrt so points don't bunch up in the middle
+        (rng.range_f64(0.0, 360.0), radius * rng.next_f64().sqrt())
+    });
+    let (sin, cos) = Deg(angle).sin_cos();
+    Ok(Primitive::FloatVec(vec![distance * cos, distance * sin]))
 }
@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+
+// splitmix64: tiny, fast, and any seed (0 included) gives a full quality sequence
+//   patterns only need the same numbers for the same seed and call order, replays depend on it
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(0)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1), from the top 53 bits so every value is exactly representable
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // uniform in [low, high)
+    pub fn range_f64(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+
+    // uniform in [low, high], both ends included
+    pub fn range_i64(&mut self, low: i64, high: i64) -> i64 {
+        let (low, high) = if low <= high {
+            (low, high)
+        } else {
+            (high, low)
+        };
+        let span = (high as i128 - low as i128 + 1) as u128;
+        // multiply-shift instead of modulo, the bias is far below anything a pattern can see
+        let offset = (self.next_u64() as u128 * span) >> 64;
+        (low as i128 + offset as i128) as i64
+    }
+}
+
+// builtins are evaluated deep inside Evaluate::eval, which only carries the Values in scope,
+// so the interpreter lends its generator to the thread for the length of a step instead of
+// threading it through every evaluation
+thread_local! {
+    static ACTIVE: RefCell<Rng> = RefCell::new(Rng::default());
+}
+
+// run f with the generator random builtins currently draw from
+pub fn with_active<T>(f: impl FnOnce(&mut Rng) -> T) -> T {
+    ACTIVE.with(|active| f(&mut active.borrow_mut()))
+}
+
+// exchange rng with the active generator: once to lend it out, once more to take it back
+fn swap_active(rng: &mut Rng) {
+    ACTIVE.with(|active| std::mem::swap(rng, &mut active.borrow_mut()));
+}
+
+// a generator lent to the thread for as long as this lives
+//   taken back on drop, a panic included, so nothing on the thread draws from it afterwards
+pub(crate) struct Lent<'a>(&'a mut Rng);
+
+impl<'a> Lent<'a> {
+    pub(crate) fn new(rng: &'a mut Rng) -> Self {
+        swap_active(rng);
+        Lent(rng)
+    }
+}
+
+impl Drop for Lent<'_> {
+    fn drop(&mut self) {
+        swap_active(self.0);
+    }
+}
@@ -0,0 +1,362 @@
+use super::entity::ExecutionEnvironment;
+use cgmath::{InnerSpace, MetricSpace, Vector2};
+use std::collections::HashMap;
+
+type Cell = (i64, i64);
+
+// uniform grid over entity positions, rebuilt by Interpreter::step whenever entities move
+//   indices are into Interpreter::entities as of the last rebuild
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    pub cell_size: f64,
+    cells: HashMap<Cell, Vec<usize>>,
+    positions: Vec<Vector2<f64>>,
+    // furthest any entity's hitbox reaches from its position, swept movement included
+    reach: f64,
+    // lowest and highest occupied cell on each axis
+    extent: Option<(Cell, Cell)>,
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        SpatialHash::new(32.0)
+    }
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f64) -> Self {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+            positions: Vec::new(),
+            reach: 0.0,
+            extent: None,
+        }
+    }
+
+    fn cell_of(&self, point: Vector2<f64>) -> Cell {
+        (
+            (point.x / self.cell_size).floor() as i64,
+            (point.y / self.cell_size).floor() as i64,
+        )
+    }
+
+    /// Re-buckets every entity by its current position. Cells that are still occupied
+    /// keep their allocations between frames.
+    pub fn rebuild(&mut self, entities: &[ExecutionEnvironment]) {
+        for bucket in self.cells.values_mut() {
+            bucket.clear();
+        }
+        self.positions.clear();
+        self.reach = 0.0;
+        for (i, environment) in entities.iter().enumerate() {
+            let entity = &environment.entity;
+            let cell = self.cell_of(entity.position);
+            self.cells.entry(cell).or_default().push(i);
+            self.positions.push(entity.position);
+
+            let size = Vector2::new(entity.hitbox.size.x as f64, entity.hitbox.size.y as f64);
+            let reach = entity.hitbox.offset.magnitude()
+                + size.magnitude() / 2.0
+                + (entity.position - environment.previous_position).magnitude();
+            self.reach = self.reach.max(reach);
+        }
+        self.cells.retain(|_, bucket| !bucket.is_empty());
+        self.extent = self
+            .cells
+            .keys()
+            .fold(None, |extent, &(x, y)| match extent {
+                Some(((min_x, min_y), (max_x, max_y))) => {
+                    Some(((x.min(min_x), y.min(min_y)), (x.max(max_x), y.max(max_y))))
+                }
+                None => Some(((x, y), (x, y))),
+            });
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn reach(&self) -> f64 {
+        self.reach
+    }
+
+    // every index bucketed in the cells overlapping the rect, unfiltered
+    //   only the part of the rect over occupied cells is walked, and when that is still more
+    //   cells than are occupied, the occupied ones are gone through instead
+    fn candidates(&self, min: Vector2<f64>, max: Vector2<f64>) -> impl Iterator<Item = usize> + '_ {
+        let ((low_x, low_y), (high_x, high_y)) = self.extent.unwrap_or(((0, 0), (-1, -1)));
+        let (min_x, min_y) = self.cell_of(min);
+        let (max_x, max_y) = self.cell_of(max);
+        let (min_x, min_y) = (min_x.max(low_x), min_y.max(low_y));
+        let (max_x, max_y) = (max_x.min(high_x), max_y.min(high_y));
+        let area =
+            (max_x as i128 - min_x as i128 + 1).max(0) * (max_y as i128 - min_y as i128 + 1).max(0);
+        let walk = area <= self.cells.len() as i128;
+        let walked = walk
+            .then(|| {
+                (min_x..=max_x)
+                    .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+                    .filter_map(move |cell| self.cells.get(&cell))
+            })
+            .into_iter()
+            .flatten();
+        let scanned = (!walk)
+            .then(|| {
+                self.cells.iter().filter_map(move |(&(x, y), bucket)| {
+                    (x >= min_x && x <= max_x && y >= min_y && y <= max_y).then_some(bucket)
+                })
+            })
+            .into_iter()
+            .flatten();
+        walked.chain(scanned).flatten().copied()
+    }
+
+    /// Entities positioned inside the rect from `min` to `max`, edges included.
+    pub fn in_rect(&self, min: Vector2<f64>, max: Vector2<f64>) -> Vec<usize> {
+        let mut found: Vec<usize> = self
+            .candidates(min, max)
+            .filter(|i| {
+                let p = self.positions[*i];
+                p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y
+            })
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    /// Entities positioned within `radius` of `point`.
+    pub fn within_radius(&self, point: Vector2<f64>, radius: f64) -> Vec<usize> {
+        let mut found: Vec<usize> = self.radius_matches(point, radius).collect();
+        found.sort_unstable();
+        found
+    }
+
+    /// Same as `within_radius(point, radius).len()` without collecting.
+    pub fn count_within_radius(&self, point: Vector2<f64>, radius: f64) -> usize {
+        self.radius_matches(point, radius).count()
+    }
+
+    fn radius_matches(&self, point: Vector2<f64>, radius: f64) -> impl Iterator<Item = usize> + '_ {
+        let corner = Vector2::new(radius, radius);
+        self.candidates(point - corner, point + corner)
+            .filter(move |i| self.positions[*i].distance2(point) <= radius * radius)
+    }
+
+    /// The entity positioned closest to `point`, lowest index on ties.
+    pub fn nearest(&self, point: Vector2<f64>) -> Option<usize> {
+        self.nearest_where(point, |_| true)
+    }
+
+    /// The closest entity to `point` that `accept` lets through.
+    pub fn nearest_where<F>(&self, point: Vector2<f64>, accept: F) -> Option<usize>
+    where
+        F: Fn(usize) -> bool,
+    {
+        if self.cells.is_empty() {
+            return None;
+        }
+        // the furthest ring that could still hold something
+        let (center_x, center_y) = self.cell_of(point);
+        let max_ring = self
+            .cells
+            .keys()
+            .map(|(x, y)| x.abs_diff(center_x).max(y.abs_diff(center_y)))
+            .max()
+            .unwrap_or(0);
+        // rings out to a point far from everything would walk more cells than there are
+        // entities to look at one by one
+        let side = 2 * max_ring as u128 + 1;
+        if side.saturating_mul(side) > self.positions.len() as u128 {
+            return self.nearest_scanned(point, accept);
+        }
+
+        let mut best: Option<(usize, f64)> = None;
+        for ring in 0..=max_ring as i64 {
+            // everything in this ring or further out is at least this far away
+            if let Some((_, distance)) = best {
+                let ring_distance = (ring - 1).max(0) as f64 * self.cell_size;
+                if ring_distance * ring_distance > distance {
+                    break;
+                }
+            }
+            for cell in ring_cells((center_x, center_y), ring) {
+                for &i in self.cells.get(&cell).into_iter().flatten() {
+                    if !accept(i) {
+                        continue;
+                    }
+                    let distance = self.positions[i].distance2(point);
+                    match best {
+                        Some((best_i, best_distance))
+                            if best_distance < distance
+                                || (best_distance == distance && best_i < i) => {}
+                        _ => best = Some((i, distance)),
+                    }
+                }
+            }
+        }
+        best.map(|(i, _)| i)
+    }
+
+    // nearest_where going through every entity, for when the rings would cover more cells
+    fn nearest_scanned<F>(&self, point: Vector2<f64>, accept: F) -> Option<usize>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let mut best: Option<(usize, f64)> = None;
+        for (i, position) in self.positions.iter().enumerate() {
+            if !accept(i) {
+                continue;
+            }
+            let distance = position.distance2(point);
+            match best {
+                Some((_, best_distance)) if best_distance <= distance => {}
+                _ => best = Some((i, distance)),
+            }
+        }
+        best.map(|(i, _)| i)
+    }
+}
+
+// the cells on the square ring `ring` cells away from center
+fn ring_cells((x, y): Cell, ring: i64) -> Vec<Cell> {
+    if ring == 0 {
+        return vec![(x, y)];
+    }
+    let mut cells = Vec::with_capacity(8 * ring as usize);
+    for dx in -ring..=ring {
+        cells.push((x.saturating_add(dx), y.saturating_sub(ring)));
+        cells.push((x.saturating_add(dx), y.saturating_add(ring)));
+    }
+    for dy in (-ring + 1)..ring {
+        cells.push((x.saturating_sub(ring), y.saturating_add(dy)));
+        cells.push((x.saturating_add(ring), y.saturating_add(dy)));
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::entity::Entity;
+    use crate::interpreter::random::Rng;
+
+    // a few hundred entities scattered around the origin, a cluster and one far outlier
+    fn scattered() -> Vec<ExecutionEnvironment> {
+        let mut rng = Rng::new(3);
+        let mut positions: Vec<Vector2<f64>> = (0..300)
+            .map(|_| Vector2::new(rng.range_f64(-400.0, 400.0), rng.range_f64(-300.0, 300.0)))
+            .collect();
+        positions.extend((0..20).map(|i| Vector2::new(50.0, 50.0 + i as f64 * 0.5)));
+        positions.push(Vector2::new(1e7, -1e7));
+        positions
+            .into_iter()
+            .map(|position| {
+                let mut entity = Entity::new();
+                entity.position = position;
+                ExecutionEnvironment::new(&entity)
+            })
+            .collect()
+    }
+
+    fn points() -> Vec<Vector2<f64>> {
+        let mut rng = Rng::new(4);
+        let mut points: Vec<Vector2<f64>> = (0..100)
+            .map(|_| Vector2::new(rng.range_f64(-600.0, 600.0), rng.range_f64(-500.0, 500.0)))
+            .collect();
+        points.extend([Vector2::new(50.0, 55.0), Vector2::new(-1e9, 1e9)]);
+        points
+    }
+
+    fn grid(entities: &[ExecutionEnvironment]) -> SpatialHash {
+        let mut grid = SpatialHash::new(32.0);
+        grid.rebuild(entities);
+        grid
+    }
+
+    fn scan<F>(entities: &[ExecutionEnvironment], keep: F) -> Vec<usize>
+    where
+        F: Fn(Vector2<f64>) -> bool,
+    {
+        (0..entities.len())
+            .filter(|&i| keep(entities[i].entity.position))
+            .collect()
+    }
+
+    #[test]
+    fn in_rect_matches_a_scan() {
+        let entities = scattered();
+        let grid = grid(&entities);
+        for point in points() {
+            for size in [0.0, 10.0, 150.0, 1e12] {
+                let (min, max) = (point, point + Vector2::new(size, size / 2.0));
+                let expected = scan(&entities, |p| {
+                    p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y
+                });
+                assert_eq!(grid.in_rect(min, max), expected, "{:?} to {:?}", min, max);
+            }
+        }
+    }
+
+    #[test]
+    fn within_radius_matches_a_scan() {
+        let entities = scattered();
+        let grid = grid(&entities);
+        for point in points() {
+            for radius in [0.0, 5.0, 64.0, 300.0, 1e12] {
+                let expected = scan(&entities, |p| p.distance2(point) <= radius * radius);
+                assert_eq!(
+                    grid.within_radius(point, radius),
+                    expected,
+                    "{:?} {}",
+                    point,
+                    radius
+                );
+                assert_eq!(grid.count_within_radius(point, radius), expected.len());
+            }
+        }
+    }
+
+    // closest accepted entity, lowest index on ties
+    fn closest<F>(
+        entities: &[ExecutionEnvironment],
+        point: Vector2<f64>,
+        accept: F,
+    ) -> Option<usize>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let distance = |i: usize| entities[i].entity.position.distance2(point);
+        (0..entities.len())
+            .filter(|&i| accept(i))
+            .fold(None, |best, i| match best {
+                Some(b) if distance(b) <= distance(i) => best,
+                _ => Some(i),
+            })
+    }
+
+    #[test]
+    fn nearest_matches_a_scan() {
+        let entities = scattered();
+        let grid = grid(&entities);
+        for point in points() {
+            assert_eq!(
+                grid.nearest(point),
+                closest(&entities, point, |_| true),
+                "{:?}",
+                point
+            );
+            assert_eq!(
+                grid.nearest_where(point, |i| i % 2 == 1),
+                closest(&entities, point, |i| i % 2 == 1),
+                "{:?}",
+                point
+            );
+        }
+        assert_eq!(SpatialHash::default().nearest(Vector2::new(0.0, 0.0)), None);
+    }
+}
@@ -0,0 +1,105 @@
+use super::entity::Hitbox;
+use crate::parser::parser::{ExpressionKind, ExpressionType, Values};
+use cgmath::{Angle, Deg, MetricSpace, Vector2};
+use std::collections::{BTreeMap, HashMap};
+
+pub type TargetId = u32;
+// ordered so the nearest target is picked the same way every run when two are tied
+pub type TargetMap = BTreeMap<TargetId, Target>;
+
+// something patterns aim at, registered and moved by the host every frame
+#[derive(Clone, Debug)]
+pub struct Target {
+    pub position: Vector2<f64>,
+    pub velocity: Option<Vector2<f64>>,
+    // what bullets have to touch to hit this target, centered on its position
+    pub hurtbox: Option<Hitbox>,
+}
+
+impl Target {
+    pub fn new(position: Vector2<f64>, velocity: Option<Vector2<f64>>) -> Self {
+        Target {
+            position,
+            velocity,
+            hurtbox: None,
+        }
+    }
+}
+
+// the player as seen from position is the nearest registered target
+pub fn nearest_target(targets: &TargetMap, position: Vector2<f64>) -> Option<&Target> {
+    let mut nearest: Option<(&Target, f64)> = None;
+    for target in targets.values() {
+        let distance = position.distance2(target.position);
+        match nearest {
+            Some((_, best)) if best <= distance => {}
+            _ => nearest = Some((target, distance)),
+        }
+    }
+    nearest.map(|(target, _)| target)
+}
+
+fn vector_as_expr(v: Vector2<f64>) -> ExpressionType {
+    ExpressionKind::Vector(vec![
+        ExpressionKind::Float(v.x).into(),
+        ExpressionKind::Float(v.y).into(),
+    ])
+    .into()
+}
+
+// the player as seen from one entity
+pub struct PlayerState {
+    // degrees from the entity to the player, in the same convention as rotation
+    pub angle: f64,
+    pub position: Vector2<f64>,
+    // zero if the host didn't give one
+    pub velocity: Vector2<f64>,
+    pub distance: f64,
+}
+
+// without any targets the player is treated as sitting on the entity, aiming at 0 degrees
+pub fn player_state(targets: &TargetMap, position: Vector2<f64>) -> PlayerState {
+    let (player_position, player_velocity) = match nearest_target(targets, position) {
+        Some(target) => (
+            target.position,
+            target.velocity.unwrap_or(Vector2::new(0.0, 0.0)),
+        ),
+        None => (position, Vector2::new(0.0, 0.0)),
+    };
+    let delta = player_position - position;
+    let angle = if delta.x == 0.0 && delta.y == 0.0 {
+        0.0
+    } else {
+        Deg::atan2(delta.y, delta.x).0
+    };
+    PlayerState {
+        angle,
+        position: player_position,
+        velocity: player_velocity,
+        distance: position.distance(player_position),
+    }
+}
+
+// player globals for an entity at position:
+//   towards_player, player_position, player_velocity, distance_to_player
+pub fn player_globals(targets: &TargetMap, position: Vector2<f64>) -> Values {
+    let player = player_state(targets, position);
+    let mut globals: Values = HashMap::new();
+    globals.insert(
+        "towards_player".to_string(),
+        ExpressionKind::Float(player.angle).into(),
+    );
+    globals.insert(
+        "player_position".to_string(),
+        vector_as_expr(player.position),
+    );
+    globals.insert(
+        "player_velocity".to_string(),
+        vector_as_expr(player.velocity),
+    );
+    globals.insert(
+        "distance_to_player".to_string(),
+        ExpressionKind::Float(player.distance).into(),
+    );
+    globals
+}
@@ -0,0 +1,246 @@
+use super::bytecode::{Instruction, Program};
+use super::evaluate::{binary_op, negate, Evaluate};
+use super::primitive::*;
+use super::ps_funcs::call_builtin;
+use crate::parser::parser::{ExpressionType, Values};
+use anyhow::Result;
+
+/// Runs a compiled expression. `inputs` must hold a value for every slot the program
+/// was compiled with, in the same order.
+pub fn execute(program: &Program, inputs: &[Primitive]) -> Result<Primitive> {
+    run(program, 0, inputs)
+}
+
+// run the program's code from start to the end
+pub(super) fn run(program: &Program, start: usize, inputs: &[Primitive]) -> Result<Primitive> {
+    let mut stack: Vec<Primitive> = Vec::new();
+    let pop = |stack: &mut Vec<Primitive>| -> Primitive {
+        stack
+            .pop()
+            .expect("compiled programs keep the stack balanced")
+    };
+    for (instruction, span) in program.code[start..]
+        .iter()
+        .zip(program.spans[start..].iter())
+    {
+        let value = match instruction {
+            Instruction::Constant(i) => program.constants[*i].clone(),
+            Instruction::Load(slot) => inputs[*slot].clone(),
+            Instruction::Negate => negate(pop(&mut stack), *span)?,
+            Instruction::Binary(op) => {
+                let rhs = pop(&mut stack);
+                let lhs = pop(&mut stack);
+                binary_op(op.clone(), lhs, rhs, *span)?
+            }
+            Instruction::Call(fn_name, argc) => {
+                let args = stack.split_off(stack.len() - argc);
+                call_builtin(fn_name, args)?
+            }
+            Instruction::Vector(length) => {
+                let elements = stack.split_off(stack.len() - length);
+                primitive_vector(elements, *span)?
+            }
+        };
+        stack.push(value);
+    }
+    Ok(pop(&mut stack))
+}
+
+// the tree walker on the same expression, environment and inputs as a compiled program
+//   kept as the reference the vm is checked against
+pub fn reference(
+    expr: &ExpressionType,
+    env: &Values,
+    slots: &[&str],
+    inputs: &[Primitive],
+) -> Result<Primitive> {
+    let mut values = env.clone();
+    for (name, input) in slots.iter().zip(inputs.iter()) {
+        if let Some(literal) = input.clone().into_expression(expr.span) {
+            values.insert(name.to_string(), literal);
+        }
+    }
+    expr.clone().eval(&values)
+}
+
+// whether the vm and the tree walker gave the same result, NaN included
+pub fn agrees(vm: &Result<Primitive>, reference: &Result<Primitive>) -> bool {
+    use Primitive::*;
+    let same_float = |a: f64, b: f64| a == b || (a.is_nan() && b.is_nan());
+    match (vm, reference) {
+        (Ok(F64(a)), Ok(F64(b))) => same_float(*a, *b),
+        (Ok(FloatVec(a)), Ok(FloatVec(b))) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_float(*a, *b))
+        }
+        (Ok(a), Ok(b)) => a == b,
+        (Err(a), Err(b)) => a.to_string() == b.to_string(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::bytecode::{compile, PATH_INPUTS};
+    use crate::parser::parser::{ArithmeticExpression, ExpressionKind, Node, Parser};
+    use crate::parser::types::Op;
+
+    // the expression in `x = ...;` of a one line path
+    fn parse(x: &str) -> ExpressionType {
+        let source = format!("path p(t) = {{ x = {}; y = 0; }}", x);
+        match Parser::parse_from_string(source) {
+            Ok(Node::Head(head)) => match head.definitions.get("p") {
+                Some(Node::Path(pd)) => pd.definitions["x"].clone(),
+                _ => panic!("no path in the source"),
+            },
+            other => panic!("`{}` didn't parse: {:?}", x, other.err()),
+        }
+    }
+
+    // for what the parser can't write yet, `>=`, `<=` and strings inside expressions
+    fn binary(op: Op, lhs: ExpressionType, rhs: ExpressionType) -> ExpressionType {
+        ExpressionKind::Expr(ArithmeticExpression::Binary(
+            op,
+            Box::new(lhs),
+            Box::new(rhs),
+        ))
+        .into()
+    }
+
+    fn string(s: &str) -> ExpressionType {
+        ExpressionKind::String(s.to_string()).into()
+    }
+
+    // parameters a = 3, b = 0.5 and v = (1, 2), with the rest of the inputs varying with t
+    fn run_both(expr: &ExpressionType, t: i64) -> (Result<Primitive>, Result<Primitive>) {
+        let mut env = Values::new();
+        env.insert("x".to_string(), expr.clone());
+        env.insert("a".to_string(), ExpressionKind::Int(3).into());
+        env.insert("b".to_string(), ExpressionKind::Float(0.5).into());
+        env.insert("v".to_string(), parse("(1, 2)"));
+        let inputs = [
+            Primitive::I64(t),
+            Primitive::F64(t as f64 * 1.5),
+            Primitive::FloatVec(vec![100.0, -t as f64]),
+            Primitive::FloatVec(vec![0.0, 2.0]),
+            Primitive::F64(40.0),
+        ];
+        let x: ExpressionType = ExpressionKind::Variable("x".to_string()).into();
+        let vm = compile(&x, &env, &PATH_INPUTS).and_then(|program| execute(&program, &inputs));
+        (vm, reference(&x, &env, &PATH_INPUTS, &inputs))
+    }
+
+    fn assert_agrees(expr: &ExpressionType) {
+        for t in [0, 1, 7, -3] {
+            let (vm, tree) = run_both(expr, t);
+            assert!(
+                agrees(&vm, &tree),
+                "{:?} at t = {}: vm gave {:?}, tree walker {:?}",
+                expr,
+                t,
+                vm,
+                tree
+            );
+        }
+    }
+
+    fn assert_both_fail(expr: &ExpressionType) {
+        let (vm, tree) = run_both(expr, 1);
+        assert!(
+            vm.is_err() && tree.is_err(),
+            "{:?}: {:?}, {:?}",
+            expr,
+            vm,
+            tree
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        for op in ["+", "-", "*", "/", "^"] {
+            assert_agrees(&parse(&format!("a {} 2", op)));
+            assert_agrees(&parse(&format!("t {} b", op)));
+            assert_agrees(&parse(&format!("b {} a", op)));
+            assert_agrees(&parse(&format!("b {} 0.25", op)));
+        }
+        assert_agrees(&parse("a + t * 2 - b / 4"));
+        assert_agrees(&parse("(a + t) * (b - 1)"));
+        assert_agrees(&binary(Op::Add, string("left"), string("right")));
+    }
+
+    #[test]
+    fn comparisons() {
+        for op in ["==", ">", "<"] {
+            assert_agrees(&parse(&format!("t {} a", op)));
+            assert_agrees(&parse(&format!("b {} t", op)));
+            assert_agrees(&parse(&format!("b {} 0.5", op)));
+        }
+        for op in [Op::GTE, Op::LTE] {
+            assert_agrees(&binary(op.clone(), parse("t"), parse("a")));
+            assert_agrees(&binary(op.clone(), parse("b"), parse("t")));
+            assert_agrees(&binary(op, parse("b"), parse("0.5")));
+        }
+        assert_agrees(&parse("t > 0 and a == 3"));
+        assert_agrees(&parse("t < 0 or b > 1"));
+    }
+
+    #[test]
+    fn negation() {
+        assert_agrees(&parse("-a"));
+        assert_agrees(&parse("-b"));
+        assert_agrees(&parse("-t * 2"));
+        assert_agrees(&parse("1 - -a"));
+    }
+
+    #[test]
+    fn vectors() {
+        assert_agrees(&parse("v + (t, 1)"));
+        assert_agrees(&parse("v * b"));
+        assert_agrees(&parse("player_position - v"));
+        assert_agrees(&parse("(a, b) * 2"));
+        assert_agrees(&parse("player_velocity / 2"));
+    }
+
+    #[test]
+    fn calls() {
+        assert_agrees(&parse("sin(t) + cos(b)"));
+        assert_agrees(&parse("max(a, t) + min(b, t)"));
+        assert_agrees(&parse("clamp(t, 0, 5)"));
+        assert_agrees(&parse("lerp(a, 10, b)"));
+        assert_agrees(&parse("length(player_position)"));
+        assert_agrees(&parse("x(rotate(v, towards_player))"));
+        assert_agrees(&parse("ease_in_out_sine(b) * distance_to_player"));
+        assert_agrees(&parse("pi * a"));
+    }
+
+    #[test]
+    fn errors() {
+        let type_errors = [
+            binary(Op::Add, parse("a"), string("text")),
+            ExpressionKind::Expr(ArithmeticExpression::Unary(
+                crate::parser::parser::UnaryOperator::Negate,
+                Box::new(string("text")),
+            ))
+            .into(),
+            parse("t and a"),
+            parse("sin(1, 2)"),
+        ];
+        for expr in &type_errors {
+            assert_both_fail(expr);
+            assert_agrees(expr);
+        }
+        // integer division by zero and overflow are errors rather than panics, even folded
+        for x in ["a / 0", "t / (a - 3)", "2 ^ 64", "a * 9223372036854775807"] {
+            assert_both_fail(&parse(x));
+            assert_agrees(&parse(x));
+        }
+        // while negative powers of ints are promoted to floats
+        assert_agrees(&parse("2 ^ -1"));
+        assert_eq!(
+            run_both(&parse("2 ^ -1"), 0).0.ok(),
+            Some(Primitive::F64(0.5))
+        );
+        assert_both_fail(&parse("nowhere + 1"));
+        assert_both_fail(&parse("unknown_builtin(t)"));
+    }
+}
@@ -1,3 +1,4 @@
-#![feature(box_patterns)]
+pub mod analysis;
+pub mod diagnostics;
 pub mod interpreter;
 pub mod parser;
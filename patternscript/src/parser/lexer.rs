@@ -1,7 +1,14 @@
-use super::types::Op;
+use super::types::{Op, Span};
+use std::fmt;
 
 #[derive(PartialEq, Debug, Clone)]
-pub enum Token {
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum TokenKind {
     Id(String),
     Number(String),
     String(String),
@@ -15,6 +22,7 @@ pub enum Token {
     RangeSeparator,
     Assign,
     Semicolon,
+    Colon,
     Keyword(Keyword),
     Condition(ConditionToken),
     LexerError(char),
@@ -28,6 +36,11 @@ pub enum Keyword {
     Bullet,
     Path,
     Pattern,
+    Fn,
+    Return,
+    Extends,
+    Run,
+    Fire,
     Let,
     Seconds,
     Frames,
@@ -39,11 +52,63 @@ pub enum ConditionToken {
     When,
 }
 
+// human readable token descriptions for diagnostics
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Id(id) => write!(f, "identifier `{}`", id),
+            TokenKind::Number(n) => write!(f, "number `{}`", n),
+            TokenKind::String(s) => write!(f, "string \"{}\"", s),
+            TokenKind::OpenParen => write!(f, "`(`"),
+            TokenKind::CloseParen => write!(f, "`)`"),
+            TokenKind::OpenBlock => write!(f, "`{{`"),
+            TokenKind::CloseBlock => write!(f, "`}}`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Operator(op) => write!(f, "operator `{}`", op),
+            TokenKind::EOF => write!(f, "end of file"),
+            TokenKind::RangeSeparator => write!(f, "`...`"),
+            TokenKind::Assign => write!(f, "`=`"),
+            TokenKind::Semicolon => write!(f, "`;`"),
+            TokenKind::Colon => write!(f, "`:`"),
+            TokenKind::Keyword(k) => write!(f, "keyword `{}`", k),
+            TokenKind::Condition(ConditionToken::Unless) => write!(f, "keyword `unless`"),
+            TokenKind::Condition(ConditionToken::When) => write!(f, "keyword `when`"),
+            TokenKind::LexerError(c) => write!(f, "unknown character `{}`", c),
+        }
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let word = match self {
+            Keyword::For => "for",
+            Keyword::Wait => "wait",
+            Keyword::Spawn => "spawn",
+            Keyword::Bullet => "bullet",
+            Keyword::Path => "path",
+            Keyword::Pattern => "pattern",
+            Keyword::Fn => "fn",
+            Keyword::Return => "return",
+            Keyword::Extends => "extends",
+            Keyword::Run => "run",
+            Keyword::Fire => "fire",
+            Keyword::Let => "let",
+            Keyword::Seconds => "seconds",
+            Keyword::Frames => "frames",
+        };
+        write!(f, "{}", word)
+    }
+}
+
 pub struct Lexer {
     source: String,
     characters: Option<Vec<char>>,
     cursor: usize,
     lookahead_cursor: usize,
+    // char index of the first character of each line
+    line_starts: Vec<usize>,
+    // byte offset of each char index, plus one past the end
+    byte_offsets: Vec<usize>,
 }
 
 impl Lexer {
@@ -53,30 +118,65 @@ impl Lexer {
             characters: None,
             cursor: 0,
             lookahead_cursor: 0,
+            line_starts: vec![0],
+            byte_offsets: Vec::new(),
         };
-        lexer.characters = Some(lexer.source.chars().collect());
+        let chars: Vec<char> = lexer.source.chars().collect();
+        for (i, c) in chars.iter().enumerate() {
+            if *c == '\n' {
+                lexer.line_starts.push(i + 1);
+            }
+        }
+        lexer.byte_offsets = lexer.source.char_indices().map(|(b, _)| b).collect();
+        lexer.byte_offsets.push(lexer.source.len());
+        lexer.characters = Some(chars);
         lexer
     }
 
-    pub fn lookahead(&mut self, n: u32) -> Option<Token> {
-        let previous_cursor = self.cursor;
-        let mut i = 0;
-        if n > 0 {
-            while i < (n - 1) {
-                let _ = self.next_token();
-                i += 1;
+    // span covering the char indices start..end
+    fn span(&self, start: usize, end: usize) -> Span {
+        let line = self.line_starts.partition_point(|s| *s <= start);
+        let column = start - self.line_starts[line - 1] + 1;
+        Span::new(
+            self.byte_offsets[start],
+            self.byte_offsets[end],
+            line,
+            column,
+        )
+    }
+
+    // whitespace and comments are not part of any token
+    fn skip_trivia(&mut self) {
+        if let Some(ref chars) = self.characters {
+            while self.cursor < chars.len() {
+                if chars[self.cursor].is_ascii_whitespace() {
+                    self.cursor += 1;
+                } else if chars[self.cursor] == '/' && chars.get(self.cursor + 1) == Some(&'/') {
+                    while self.cursor < chars.len() && chars[self.cursor] != '\n' {
+                        self.cursor += 1;
+                    }
+                } else {
+                    break;
+                }
             }
         }
-        let result = self.next_token();
-        self.cursor = previous_cursor;
-        result
     }
 
     pub fn next_token(&mut self) -> Option<Token> {
+        self.skip_trivia();
+        let start = self.cursor;
+        let kind = self.next_kind()?;
+        Some(Token {
+            kind,
+            span: self.span(start, self.cursor),
+        })
+    }
+
+    fn next_kind(&mut self) -> Option<TokenKind> {
         match self.characters {
             Some(ref chars) => {
                 if self.cursor >= chars.len() {
-                    return Some(Token::EOF);
+                    return Some(TokenKind::EOF);
                 }
                 self.lookahead_cursor = self.cursor + 1;
                 let initial = chars[self.cursor];
@@ -114,45 +214,44 @@ impl Lexer {
                     word == found
                 };
 
-                let token: Token = match initial {
-                    '(' => Token::OpenParen,
-                    ')' => Token::CloseParen,
-                    '{' => Token::OpenBlock,
-                    '}' => Token::CloseBlock,
-                    ',' => Token::Comma,
-                    ';' => Token::Semicolon,
-                    '+' => Token::Operator(Op::Add),
-                    '-' => Token::Operator(Op::Sub),
-                    '*' => Token::Operator(Op::Mul),
-                    '^' => Token::Operator(Op::Exp),
-                    '>' => Token::Operator(Op::GT),
-                    '<' => Token::Operator(Op::LT),
-                    _ if exact_match("==") => Token::Operator(Op::Test),
-                    _ if exact_match(">=") => Token::Operator(Op::GTE),
-                    _ if exact_match("<=") => Token::Operator(Op::LTE),
-                    '=' => Token::Assign,
-                    _ if exact_match("//") => {
-                        while chars[self.lookahead_cursor] != '\n' {
-                            self.lookahead_cursor += 1;
-                        }
-                        self.cursor = self.lookahead_cursor + 1;
-                        return self.next_token();
-                    }
-                    '/' => Token::Operator(Op::Div),
-                    _ if exact_match("for") => Token::Keyword(Keyword::For),
-                    _ if exact_match("...") => Token::RangeSeparator,
-                    _ if exact_match("and") => Token::Operator(Op::And),
-                    _ if exact_match("or") => Token::Operator(Op::Or),
-                    _ if exact_match("unless") => Token::Condition(ConditionToken::Unless),
-                    _ if exact_match("when") => Token::Condition(ConditionToken::When),
-                    _ if exact_match("wait") => Token::Keyword(Keyword::Wait),
-                    _ if exact_match("spawn") => Token::Keyword(Keyword::Spawn),
-                    _ if exact_match("bullet") => Token::Keyword(Keyword::Bullet),
-                    _ if exact_match("path") => Token::Keyword(Keyword::Path),
-                    _ if exact_match("pattern") => Token::Keyword(Keyword::Pattern),
-                    _ if exact_match("let") => Token::Keyword(Keyword::Let),
-                    _ if exact_match("seconds") => Token::Keyword(Keyword::Seconds),
-                    _ if exact_match("frames") => Token::Keyword(Keyword::Frames),
+                let token: TokenKind = match initial {
+                    '(' => TokenKind::OpenParen,
+                    ')' => TokenKind::CloseParen,
+                    '{' => TokenKind::OpenBlock,
+                    '}' => TokenKind::CloseBlock,
+                    ',' => TokenKind::Comma,
+                    ';' => TokenKind::Semicolon,
+                    ':' => TokenKind::Colon,
+                    '+' => TokenKind::Operator(Op::Add),
+                    '-' => TokenKind::Operator(Op::Sub),
+                    '*' => TokenKind::Operator(Op::Mul),
+                    '^' => TokenKind::Operator(Op::Exp),
+                    '>' => TokenKind::Operator(Op::GT),
+                    '<' => TokenKind::Operator(Op::LT),
+                    _ if exact_match("==") => TokenKind::Operator(Op::Test),
+                    _ if exact_match(">=") => TokenKind::Operator(Op::GTE),
+                    _ if exact_match("<=") => TokenKind::Operator(Op::LTE),
+                    '=' => TokenKind::Assign,
+                    '/' => TokenKind::Operator(Op::Div),
+                    _ if exact_match("for") => TokenKind::Keyword(Keyword::For),
+                    _ if exact_match("...") => TokenKind::RangeSeparator,
+                    _ if exact_match("and") => TokenKind::Operator(Op::And),
+                    _ if exact_match("or") => TokenKind::Operator(Op::Or),
+                    _ if exact_match("unless") => TokenKind::Condition(ConditionToken::Unless),
+                    _ if exact_match("when") => TokenKind::Condition(ConditionToken::When),
+                    _ if exact_match("wait") => TokenKind::Keyword(Keyword::Wait),
+                    _ if exact_match("spawn") => TokenKind::Keyword(Keyword::Spawn),
+                    _ if exact_match("bullet") => TokenKind::Keyword(Keyword::Bullet),
+                    _ if exact_match("path") => TokenKind::Keyword(Keyword::Path),
+                    _ if exact_match("pattern") => TokenKind::Keyword(Keyword::Pattern),
+                    _ if exact_match("fn") => TokenKind::Keyword(Keyword::Fn),
+                    _ if exact_match("return") => TokenKind::Keyword(Keyword::Return),
+                    _ if exact_match("extends") => TokenKind::Keyword(Keyword::Extends),
+                    _ if exact_match("run") => TokenKind::Keyword(Keyword::Run),
+                    _ if exact_match("fire") => TokenKind::Keyword(Keyword::Fire),
+                    _ if exact_match("let") => TokenKind::Keyword(Keyword::Let),
+                    _ if exact_match("seconds") => TokenKind::Keyword(Keyword::Seconds),
+                    _ if exact_match("frames") => TokenKind::Keyword(Keyword::Frames),
 
                     number if initial.is_digit(10) => {
                         let mut full_number: String = String::new();
@@ -168,7 +267,7 @@ impl Lexer {
                             // handle case x...y where x. .. y is wrong
                             if chars[self.lookahead_cursor + 1] == '.' {
                                 self.cursor = self.lookahead_cursor;
-                                return Some(Token::Number(full_number));
+                                return Some(TokenKind::Number(full_number));
                             }
                             full_number.push(chars[self.lookahead_cursor]);
                             self.lookahead_cursor += 1;
@@ -179,7 +278,7 @@ impl Lexer {
                         }
 
                         self.cursor = self.lookahead_cursor - 1;
-                        Token::Number(full_number)
+                        TokenKind::Number(full_number)
                     }
                     '"' => {
                         let mut full_string: String = String::new();
@@ -196,7 +295,7 @@ impl Lexer {
                             self.lookahead_cursor += 1;
                         }
                         self.cursor = self.lookahead_cursor;
-                        Token::String(full_string)
+                        TokenKind::String(full_string)
                     }
                     c if initial.is_ascii_alphabetic() => {
                         let mut full_id: String = String::new();
@@ -211,13 +310,9 @@ impl Lexer {
 
                         self.cursor = self.lookahead_cursor - 1;
 
-                        Token::Id(full_id)
-                    }
-                    _ if initial.is_ascii_whitespace() => {
-                        self.cursor += 1;
-                        return self.next_token();
+                        TokenKind::Id(full_id)
                     }
-                    c => Token::LexerError(c),
+                    c => TokenKind::LexerError(c),
                 };
                 self.cursor += 1;
                 Some(token)
@@ -226,3 +321,50 @@ impl Lexer {
         }
     }
 }
+
+// the whole file lexed up front so the parser can look any distance ahead in constant time
+pub struct TokenStream {
+    // always ends in a single EOF token
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl TokenStream {
+    pub fn new(mut lexer: Lexer) -> TokenStream {
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            let eof = token.kind == TokenKind::EOF;
+            tokens.push(token);
+            if eof {
+                break;
+            }
+        }
+        if tokens
+            .last()
+            .map(|t| t.kind != TokenKind::EOF)
+            .unwrap_or(true)
+        {
+            tokens.push(Token {
+                kind: TokenKind::EOF,
+                span: Span::default(),
+            });
+        }
+        TokenStream {
+            tokens,
+            position: 0,
+        }
+    }
+
+    // n = 1 is the next token; looking past the end gives the EOF token
+    pub fn peek(&self, n: usize) -> &Token {
+        let index = (self.position + n.max(1) - 1).min(self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
+    // consumes the next token; None once the EOF token has already been consumed
+    pub fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position)?.clone();
+        self.position += 1;
+        Some(token)
+    }
+}
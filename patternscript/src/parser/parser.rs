@@ -1,5 +1,6 @@
-use super::lexer::{ConditionToken, Keyword, Lexer, Token};
-use super::types::Op;
+use super::lexer::{ConditionToken, Keyword, Lexer, Token, TokenKind, TokenStream};
+use super::types::{Op, Span};
+use crate::diagnostics::Diagnostic;
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 //use std::fmt;
@@ -7,26 +8,49 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ParseError {
-    #[error("Unexpected token {0:?}")]
+    #[error("{}: Unexpected token {:?}", .0.span, .0.kind)]
     Token(Token),
-    #[error("Expected token {0:?} but got token {1:?}")]
-    Expected(Token, Token),
-    #[error("Unexpected EOF.")]
-    EOF,
-    #[error("Only definitions allowed in this block.")]
-    Definitions,
-    #[error("Invalid number.")]
-    InvalidNumber,
-    #[error("For definitions must be in the form of a = 1, b = 2...")]
-    InvalidForDef,
-    #[error("Range must be two Ints, not Floats.")]
-    RangeMustBeInt,
-    #[error("Bad vector element.")]
-    BadVecElement,
-    #[error("Time error: frames must be integers, seconds must be numeric.")]
-    TimeTypeError,
-    #[error("{0}")]
-    NeedsClearerError(&'static str),
+    #[error("{}: Expected token {:?} but got token {:?}", .1.span, .0, .1.kind)]
+    Expected(TokenKind, Token),
+    #[error("{}: Expected {} but got token {:?}", .1.span, .0, .1.kind)]
+    ExpectedDescribed(&'static str, Token),
+    #[error("{0}: Unexpected EOF.")]
+    EOF(Span),
+    #[error("{0}: Only definitions allowed in this block.")]
+    Definitions(Span),
+    #[error("{0}: Invalid number.")]
+    InvalidNumber(Span),
+    #[error("{0}: For definitions must be in the form of a = 1, b = 2...")]
+    InvalidForDef(Span),
+    #[error("{0}: Range must be two Ints, not Floats.")]
+    RangeMustBeInt(Span),
+    #[error("{0}: Bad vector element.")]
+    BadVecElement(Span),
+    #[error("{0}: Time error: frames must be integers, seconds must be numeric.")]
+    TimeTypeError(Span),
+    #[error("{1}: {0}")]
+    NeedsClearerError(&'static str, Span),
+    #[error("{1}: {0} is already defined at {2}")]
+    Redefinition(String, Span, Span),
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::Token(t)
+            | ParseError::Expected(_, t)
+            | ParseError::ExpectedDescribed(_, t) => t.span,
+            ParseError::EOF(s)
+            | ParseError::Definitions(s)
+            | ParseError::InvalidNumber(s)
+            | ParseError::InvalidForDef(s)
+            | ParseError::RangeMustBeInt(s)
+            | ParseError::BadVecElement(s)
+            | ParseError::TimeTypeError(s)
+            | ParseError::NeedsClearerError(_, s)
+            | ParseError::Redefinition(_, s, _) => *s,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,16 +64,39 @@ pub type Values = HashMap<String, ExpressionType>;
 pub enum ArithmeticExpression {
     Unary(UnaryOperator, Box<ExpressionType>),
     Binary(Op, Box<ExpressionType>, Box<ExpressionType>),
+    // name of the function and its arguments as written, f((1, 2)) has one and f(1, 2) two
+    Call(String, Vec<ExpressionType>),
 }
 
 #[derive(Debug, Clone)]
 pub enum UnaryOperator {
     Negate,
-    FunctionCall(String), // Unary(FunctionCall(name of function), (boxed args vec: see above enum))
 }
 
+// an expression and where it was written; values created at runtime have a default span
 #[derive(Debug, Clone)]
-pub enum ExpressionType {
+pub struct ExpressionType {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+impl ExpressionType {
+    pub fn new(kind: ExpressionKind, span: Span) -> ExpressionType {
+        ExpressionType { kind, span }
+    }
+}
+
+impl From<ExpressionKind> for ExpressionType {
+    fn from(kind: ExpressionKind) -> ExpressionType {
+        ExpressionType {
+            kind,
+            span: Span::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
     Int(i64),
     Float(f64),
     String(String),
@@ -62,41 +109,76 @@ pub enum ExpressionType {
     None,
 }
 
+// ordered statements, including assignments, executed top to bottom
 #[derive(Debug, Clone)]
 pub struct Block {
-    pub definitions: Values,
     pub statements: Vec<Node>,
+    pub span: Span,
 }
 
 impl Block {
     pub fn new() -> Block {
         Block {
-            definitions: HashMap::new(),
             statements: Vec::new(),
+            span: Span::default(),
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct PatternData {
-    pub block: Block,
+    // parameters given by run and fire, an empty vector when it takes none
+    pub arguments: ExpressionType,
+    // `pattern b extends a`, b's definitions are laid over a's
+    pub parent: Option<String>,
+    pub definitions: Values,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct BulletData {
+    // `bullet name : parent`, whose fields this one starts from
+    pub parent: Option<String>,
     pub definitions: Values,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct PathData {
+    // always a vector of variables, one per parameter
     pub arguments: ExpressionType,
     pub definitions: Values,
+    pub span: Span,
+}
+
+// `fn name(a, b) = expr;`, or a block of assignments ending in `return expr;`
+#[derive(Debug, Clone)]
+pub struct FunctionData {
+    // always a vector of variables, one per parameter
+    pub arguments: ExpressionType,
+    pub body: ExpressionType,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReturnData {
+    pub value: ExpressionType,
+    pub span: Span,
+}
+
+// `run name(args);` and `fire name(args);`, the arguments can be left off when there are none
+#[derive(Debug, Clone)]
+pub struct PatternCallData {
+    pub pattern: String,
+    pub arguments: Vec<ExpressionType>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct AssignmentData {
     pub lvalue: String,
     pub rvalue: ExpressionType,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -105,6 +187,14 @@ pub enum WaitData {
     Time(ExpressionType),
 }
 
+impl WaitData {
+    pub fn span(&self) -> Span {
+        match self {
+            WaitData::Frames(e) | WaitData::Time(e) => e.span,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Condition {
     When(ExpressionType),
@@ -117,11 +207,13 @@ pub struct ForData {
     pub initial_definitions: Values,
     pub condition: Condition,
     pub body: Block,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct SpawnData {
     pub definitions: Values,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -130,16 +222,45 @@ pub enum Node {
     Pattern(PatternData),
     Bullet(BulletData),
     Path(PathData),
+    Function(FunctionData),
     Wait(WaitData),
     For(ForData),
     // Expression(ExpressionType),
     Spawn(SpawnData),
+    Assign(AssignmentData),
+    Return(ReturnData),
+    // the sub-pattern runs in place, seeing the variables in scope, and finishes first
+    Run(PatternCallData),
+    // the sub-pattern starts on an emitter of its own, seeing only its own definitions
+    Fire(PatternCallData),
+}
+
+impl Node {
+    pub fn span(&self) -> Span {
+        match self {
+            Node::Head(_) => Span::default(),
+            Node::Pattern(pd) => pd.span,
+            Node::Bullet(bd) => bd.span,
+            Node::Path(pd) => pd.span,
+            Node::Function(fd) => fd.span,
+            Node::Wait(wd) => wd.span(),
+            Node::For(fd) => fd.span,
+            Node::Spawn(sd) => sd.span,
+            Node::Assign(ad) => ad.span,
+            Node::Return(rd) => rd.span,
+            Node::Run(cd) | Node::Fire(cd) => cd.span,
+        }
+    }
 }
 
 type NamedToplevel = (String, Node);
 
 pub struct Parser {
-    lexer: Lexer,
+    tokens: TokenStream,
+    // span of the most recently consumed token, used to close node spans
+    previous_span: Span,
+    // errors recovered from so far; parsing continues past each one
+    errors: Vec<anyhow::Error>,
 }
 
 impl Parser {
@@ -151,193 +272,380 @@ impl Parser {
         Parser::parse_from_string(std::fs::read_to_string(path)?)
     }
 
+    /// Parses the source, returning structured diagnostics instead of an error message on failure.
+    /// Render them with `Diagnostic::render` against the same source.
+    pub fn parse_with_diagnostics(source: String) -> std::result::Result<Node, Vec<Diagnostic>> {
+        let (head, errors) = Parser::new(Lexer::new(source)).parse_recovering();
+        if errors.is_empty() {
+            Ok(Node::Head(head))
+        } else {
+            Err(errors.iter().map(Diagnostic::from_error).collect())
+        }
+    }
+
     pub fn new(lexer: Lexer) -> Parser {
-        Parser { lexer }
+        Parser {
+            tokens: TokenStream::new(lexer),
+            previous_span: Span::default(),
+            errors: Vec::new(),
+        }
     }
 
     fn next_token(&mut self) -> Result<Token> {
-        self.lexer
-            .next_token()
-            .ok_or_else(|| ParseError::EOF.into())
+        let t = self
+            .tokens
+            .advance()
+            .ok_or(ParseError::EOF(self.previous_span))?;
+        self.previous_span = t.span;
+        Ok(t)
     }
 
-    fn lookahead(&mut self, n: u32) -> Result<Token> {
-        self.lexer
-            .lookahead(n)
-            .ok_or_else(|| ParseError::EOF.into())
+    fn lookahead(&self, n: usize) -> Result<Token> {
+        Ok(self.tokens.peek(n).clone())
     }
 
-    fn expect_next(&mut self, expected: Token) -> Result<Token> {
+    fn expect_next(&mut self, expected: TokenKind) -> Result<Token> {
         let t = self.next_token()?;
-        if t != expected {
+        if t.kind != expected {
             return Err(ParseError::Expected(expected, t).into());
         }
         Ok(t)
     }
 
+    // span from start through the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous_span)
+    }
+
+    // fails with the first error found, see parse_recovering for all of them
     pub fn evaluate(&mut self) -> Result<Node> {
-        self.parse_head()
+        let head = self.parse_head()?;
+        if self.errors.is_empty() {
+            Ok(head)
+        } else {
+            Err(self.errors.remove(0))
+        }
+    }
+
+    /// Parses the whole file, recovering from errors by skipping to the end of the broken
+    /// statement or definition. Returns everything that parsed along with every error found.
+    pub fn parse_recovering(&mut self) -> (HeadData, Vec<anyhow::Error>) {
+        let head = match self.parse_head() {
+            Ok(Node::Head(hd)) => hd,
+            Ok(_) => unreachable!("parse_head always produces a head node"),
+            Err(e) => {
+                self.errors.push(e);
+                HeadData {
+                    definitions: HashMap::new(),
+                }
+            }
+        };
+        (head, std::mem::take(&mut self.errors))
     }
 
     fn parse_head(&mut self) -> Result<Node> {
-        let mut head = Node::Head(HeadData {
+        let mut head = HeadData {
             definitions: HashMap::new(),
-        });
-        while let Some(token) = self.lexer.next_token() {
-            let (name, node) = match token {
-                Token::EOF => {
+        };
+        loop {
+            let token = self.next_token()?;
+            let parsed = match token.kind {
+                TokenKind::EOF => {
                     break;
                 }
-                Token::Keyword(Keyword::Pattern) => self.parse_pattern()?,
-                Token::Keyword(Keyword::Path) => self.parse_path()?,
-                Token::Keyword(Keyword::Bullet) => self.parse_bullet()?,
+                TokenKind::Keyword(Keyword::Pattern) => self.parse_pattern(token.span),
+                TokenKind::Keyword(Keyword::Path) => self.parse_path(token.span),
+                TokenKind::Keyword(Keyword::Bullet) => self.parse_bullet(token.span),
+                TokenKind::Keyword(Keyword::Fn) => self.parse_function(token.span),
+                _ => Err(ParseError::Token(token).into()),
+            };
+            match parsed {
+                // patterns, bullets, paths and functions share one namespace, and the first
+                // definition of a name is kept rather than quietly replaced by a later one
+                Ok((name, node)) => match head.definitions.get(&name) {
+                    Some(first) => {
+                        let error = ParseError::Redefinition(name, node.span(), first.span());
+                        self.errors.push(error.into());
+                    }
+                    None => {
+                        head.definitions.insert(name, node);
+                    }
+                },
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize_toplevel()?;
+                }
+            }
+        }
+        Ok(Node::Head(head))
+    }
+
+    // tokens which can only begin a top level definition; a block that reaches one is unclosed
+    fn is_toplevel_boundary(kind: &TokenKind) -> bool {
+        matches!(
+            kind,
+            TokenKind::EOF
+                | TokenKind::Keyword(Keyword::Pattern)
+                | TokenKind::Keyword(Keyword::Bullet)
+                | TokenKind::Keyword(Keyword::Path)
+                | TokenKind::Keyword(Keyword::Fn)
+        )
+    }
+
+    // records a missing } if the next token can't be inside the current block
+    fn at_block_boundary(&mut self) -> Result<bool> {
+        let t = self.lookahead(1)?;
+        if Parser::is_toplevel_boundary(&t.kind) {
+            self.errors
+                .push(ParseError::Expected(TokenKind::CloseBlock, t).into());
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // panic mode recovery: skip the rest of a broken statement, stopping after its ; or its
+    // braced body, before the } closing the enclosing block, or at the next top level keyword
+    fn synchronize(&mut self) -> Result<()> {
+        let mut depth = 0;
+        loop {
+            let t = self.lookahead(1)?;
+            match t.kind {
+                _ if Parser::is_toplevel_boundary(&t.kind) => return Ok(()),
+                TokenKind::CloseBlock if depth == 0 => return Ok(()),
+                TokenKind::Semicolon if depth == 0 => {
+                    self.next_token()?;
+                    return Ok(());
+                }
+                TokenKind::CloseBlock => {
+                    self.next_token()?;
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                TokenKind::OpenBlock => {
+                    self.next_token()?;
+                    depth += 1;
+                }
                 _ => {
-                    return Err(ParseError::Token(token).into());
+                    self.next_token()?;
                 }
-            };
-            if let Node::Head(ref mut head) = head {
-                head.definitions.insert(name, node);
             }
         }
-        Ok(head)
     }
 
-    fn parse_pattern(&mut self) -> Result<NamedToplevel> {
+    // skip to the start of the next pattern, bullet, path or function
+    fn synchronize_toplevel(&mut self) -> Result<()> {
+        while !Parser::is_toplevel_boundary(&self.lookahead(1)?.kind) {
+            self.next_token()?;
+        }
+        Ok(())
+    }
+
+    fn parse_pattern(&mut self, start: Span) -> Result<NamedToplevel> {
         let name = self.next_token().context("Parsing pattern...")?;
-        if let Token::Id(name) = name {
-            self.expect_next(Token::Assign)?;
-            let block = self.parse_block()?;
-            let pattern_node = Node::Pattern(PatternData { block });
+        if let TokenKind::Id(name) = name.kind {
+            let arguments = if self.lookahead(1)?.kind == TokenKind::OpenParen {
+                self.parse_parameters()?
+            } else {
+                ExpressionType::new(ExpressionKind::Vector(Vec::new()), self.previous_span)
+            };
+            let parent = if self.lookahead(1)?.kind == TokenKind::Keyword(Keyword::Extends) {
+                self.next_token()?;
+                let parent = self.next_token()?;
+                match parent.kind {
+                    TokenKind::Id(parent) => Some(parent),
+                    _ => return Err(ParseError::ExpectedDescribed("a pattern name", parent).into()),
+                }
+            } else {
+                None
+            };
+            self.expect_next(TokenKind::Assign)?;
+            let definitions = self.parse_values()?;
+            let pattern_node = Node::Pattern(PatternData {
+                arguments,
+                parent,
+                definitions,
+                span: self.span_from(start),
+            });
             Ok((name, pattern_node))
         } else {
-            Err(ParseError::Expected(Token::String("Id".to_string()), name).into())
+            Err(ParseError::ExpectedDescribed("an identifier", name).into())
         }
     }
 
     fn parse_block(&mut self) -> Result<Block> {
-        self.expect_next(Token::OpenBlock)
+        let open = self
+            .expect_next(TokenKind::OpenBlock)
             .context("Parsing block...")?;
 
         let mut block = Block {
-            definitions: HashMap::new(),
             statements: Vec::new(),
+            span: open.span,
         };
 
-        let mut t = self.next_token()?;
-        while t != Token::CloseBlock {
-            // id, wait, for, spawn
-            match t {
-                Token::Id(id) => {
-                    self.expect_next(Token::Assign)?;
-                    let expression = self.parse_expression()?;
-                    block.definitions.insert(id, expression);
-                }
-                Token::Keyword(Keyword::For) => {
-                    let for_data = self.parse_for()?;
-                    block.statements.push(Node::For(for_data));
-                }
-                Token::Keyword(Keyword::Wait) => {
-                    let wait = self.parse_wait()?;
-                    block.statements.push(Node::Wait(wait));
-                }
-                Token::Keyword(Keyword::Spawn) => {
-                    let spawn = self.parse_spawn()?;
-                    block.statements.push(Node::Spawn(spawn));
-                }
-                _ => return Err(ParseError::Token(t).into()),
+        while !self.at_block_boundary()? {
+            let t = self.next_token()?;
+            if t.kind == TokenKind::CloseBlock {
+                break;
+            }
+            if let Err(e) = self.parse_statement(t, &mut block) {
+                self.errors.push(e);
+                self.synchronize()?;
             }
-            t = self.next_token()?;
         }
+        block.span = self.span_from(open.span);
         Ok(block)
     }
 
+    fn parse_statement(&mut self, t: Token, block: &mut Block) -> Result<()> {
+        // id, wait, for, spawn, run, fire, return
+        match t.kind {
+            TokenKind::Id(id) => {
+                self.expect_next(TokenKind::Assign)?;
+                let rvalue = self.parse_expression()?;
+                block.statements.push(Node::Assign(AssignmentData {
+                    lvalue: id,
+                    rvalue,
+                    span: self.span_from(t.span),
+                }));
+            }
+            TokenKind::Keyword(Keyword::For) => {
+                let for_data = self.parse_for(t.span)?;
+                block.statements.push(Node::For(for_data));
+            }
+            TokenKind::Keyword(Keyword::Wait) => {
+                let wait = self.parse_wait()?;
+                block.statements.push(Node::Wait(wait));
+            }
+            TokenKind::Keyword(Keyword::Spawn) => {
+                let spawn = self.parse_spawn(t.span)?;
+                block.statements.push(Node::Spawn(spawn));
+            }
+            TokenKind::Keyword(Keyword::Run) => {
+                let call = self.parse_pattern_call(t.span)?;
+                block.statements.push(Node::Run(call));
+            }
+            TokenKind::Keyword(Keyword::Fire) => {
+                let call = self.parse_pattern_call(t.span)?;
+                block.statements.push(Node::Fire(call));
+            }
+            TokenKind::Keyword(Keyword::Return) => {
+                let value = self.parse_expression()?;
+                block.statements.push(Node::Return(ReturnData {
+                    value,
+                    span: self.span_from(t.span),
+                }));
+            }
+            _ => return Err(ParseError::Token(t).into()),
+        }
+        Ok(())
+    }
+
     // faster case for block when no imperative/ordered actions
     fn parse_values(&mut self) -> Result<Values> {
-        self.expect_next(Token::OpenBlock)
+        self.expect_next(TokenKind::OpenBlock)
             .context("Parsing values...")?;
 
         let mut definitions = HashMap::new();
-        let mut t = self.next_token()?;
-        while t != Token::CloseBlock {
-            match t {
-                Token::Id(id) => {
-                    self.expect_next(Token::Assign)?;
-                    let expression = self.parse_expression()?;
-                    definitions.insert(id, expression);
-                }
-                _ => return Err(ParseError::Definitions.into()),
+        while !self.at_block_boundary()? {
+            let t = self.next_token()?;
+            if t.kind == TokenKind::CloseBlock {
+                break;
+            }
+            if let Err(e) = self.parse_definition(t, &mut definitions) {
+                self.errors.push(e);
+                self.synchronize()?;
             }
-            t = self.next_token()?;
         }
 
         Ok(definitions)
     }
 
+    fn parse_definition(&mut self, t: Token, definitions: &mut Values) -> Result<()> {
+        match t.kind {
+            TokenKind::Id(id) => {
+                self.expect_next(TokenKind::Assign)?;
+                let expression = self.parse_expression()?;
+                definitions.insert(id, expression);
+                Ok(())
+            }
+            _ => Err(ParseError::Definitions(t.span).into()),
+        }
+    }
+
     // note: excellent article https://www.engr.mun.ca/~theo/Misc/exp_parsing.htm with details about
     //       three approaches to this problem, cited papers are also extremely helpful in the
     //       bibliography of the linked post.
     fn parse_expression(&mut self) -> Result<ExpressionType> {
-        let expr = self.parse_expression_r();
+        let expr = self.parse_expression_r()?;
         // special case for pseudo-datatypes
-        match self.lookahead(1)? {
-            Token::Keyword(Keyword::Seconds) => {
+        let next = self.lookahead(1)?;
+        match &next.kind {
+            TokenKind::Keyword(Keyword::Seconds) => {
                 self.next_token()?;
-                self.expect_next(Token::Semicolon)?;
-                let expr = expr?;
-                if matches!(expr, ExpressionType::Int(_) | ExpressionType::Float(_)) {
-                    Ok(ExpressionType::Duration(Box::new(WaitData::Time(expr))))
+                let span = self.span_from(expr.span);
+                self.expect_next(TokenKind::Semicolon)?;
+                if matches!(expr.kind, ExpressionKind::Int(_) | ExpressionKind::Float(_)) {
+                    Ok(ExpressionType::new(
+                        ExpressionKind::Duration(Box::new(WaitData::Time(expr))),
+                        span,
+                    ))
                 } else {
-                    Err(ParseError::TimeTypeError.into())
+                    Err(ParseError::TimeTypeError(expr.span).into())
                 }
             }
-            Token::Keyword(Keyword::Frames) => {
+            TokenKind::Keyword(Keyword::Frames) => {
                 self.next_token()?;
-                self.expect_next(Token::Semicolon)?;
-                let expr = expr?;
-                if matches!(expr, ExpressionType::Int(_)) {
-                    Ok(ExpressionType::Duration(Box::new(WaitData::Frames(expr))))
+                let span = self.span_from(expr.span);
+                self.expect_next(TokenKind::Semicolon)?;
+                if matches!(expr.kind, ExpressionKind::Int(_)) {
+                    Ok(ExpressionType::new(
+                        ExpressionKind::Duration(Box::new(WaitData::Frames(expr))),
+                        span,
+                    ))
                 } else {
-                    Err(ParseError::TimeTypeError.into())
+                    Err(ParseError::TimeTypeError(expr.span).into())
                 }
             }
-            Token::Semicolon => {
+            TokenKind::Semicolon => {
                 self.next_token()?;
-                expr
+                Ok(expr)
             }
-            Token::CloseBlock => {
+            TokenKind::CloseBlock => {
                 // leave token to be consumed by parse_block
-                expr
+                Ok(expr)
             }
-            Token::Assign => {
+            TokenKind::Assign => {
                 // leave token to be consumed by parse_path
-                expr
+                Ok(expr)
             }
-            Token::CloseParen | Token::Comma => {
+            TokenKind::CloseParen | TokenKind::Comma => {
                 // leave to be consumed by op_or_vec
-                expr
+                Ok(expr)
             }
-            _x => {
-                return Err(
-                    ParseError::NeedsClearerError("Expressions should end in } or ;.").into(),
-                );
+            kind if Parser::is_toplevel_boundary(kind) => {
+                // unclosed block, leave to be reported by parse_block
+                Ok(expr)
             }
+            _ => Err(ParseError::ExpectedDescribed("`;` or `}` after expression", next).into()),
         }
     }
     // inner recursive loop in the case of vectors
     fn parse_expression_r(&mut self) -> Result<ExpressionType> {
         let t = self.lookahead(1)?;
-        match t {
-            Token::OpenBlock => {
+        match t.kind {
+            TokenKind::OpenBlock => {
                 // self.next_token()?;
-                return Ok(ExpressionType::Block(self.parse_block()?));
+                let block = self.parse_block()?;
+                let span = block.span;
+                Ok(ExpressionType::new(ExpressionKind::Block(block), span))
             }
-            Token::String(s) => {
+            TokenKind::String(s) => {
                 self.next_token()?;
-                return Ok(ExpressionType::String(s));
-            }
-            _ => {
-                return self.parse_expression_p(0);
+                Ok(ExpressionType::new(ExpressionKind::String(s), t.span))
             }
+            _ => self.parse_expression_p(0),
         }
     }
     // precedence handling -- the meat
@@ -346,8 +654,8 @@ impl Parser {
         let mut tree = self.parse_operator_or_value()?;
         loop {
             let next = self.lookahead(1)?;
-            match next {
-                Token::Operator(op) => {
+            match next.kind {
+                TokenKind::Operator(op) => {
                     let p = self.operator_precedence(&op);
                     if p < precedence {
                         break;
@@ -360,11 +668,15 @@ impl Parser {
                         _ => p,
                     };
                     let rhs = self.parse_expression_p(new_precedence)?;
-                    tree = ExpressionType::Expr(ArithmeticExpression::Binary(
-                        op,
-                        Box::new(tree),
-                        Box::new(rhs),
-                    ));
+                    let span = tree.span.to(rhs.span);
+                    tree = ExpressionType::new(
+                        ExpressionKind::Expr(ArithmeticExpression::Binary(
+                            op,
+                            Box::new(tree),
+                            Box::new(rhs),
+                        )),
+                        span,
+                    );
                 }
                 _ => break,
             }
@@ -394,170 +706,173 @@ impl Parser {
 
     fn parse_operator_or_value(&mut self) -> Result<ExpressionType> {
         // lookahead then consume on branch
-        let mut t = self.lookahead(1)?;
-        if matches!(t, Token::Operator(Op::Sub)) {
+        let t = self.lookahead(1)?;
+        let start = t.span;
+        if matches!(t.kind, TokenKind::Operator(Op::Sub)) {
             // unary - precedence 4 left assoc
             self.next_token()?;
             let expr = self.parse_expression_p(4)?;
-            Ok(ExpressionType::Expr(ArithmeticExpression::Unary(
-                UnaryOperator::Negate,
-                Box::new(expr),
-            )))
-        } else if t == Token::OpenParen {
-            // in the outermost parenthesis loop, are we a vector?
+            Ok(ExpressionType::new(
+                ExpressionKind::Expr(ArithmeticExpression::Unary(
+                    UnaryOperator::Negate,
+                    Box::new(expr),
+                )),
+                self.span_from(start),
+            ))
+        } else if t.kind == TokenKind::OpenParen {
+            // the token after the first element decides if we're a vector
             // (1, 2)       -- yes
             // ((x + 1), 2) -- yes
             // ((x + 1)- 2) -- no
-            let mut found_vector = false;
-            let mut nested_paren_level = 0;
-            let mut lookahead_n = 1; // t (open paren), we start at next
-            while !(t == Token::CloseParen && nested_paren_level < 1) {
-                t = self.lookahead(lookahead_n)?;
-                if t == Token::OpenParen {
-                    nested_paren_level += 1;
-                }
-                if t == Token::CloseParen {
-                    nested_paren_level -= 1;
-                }
-                if t == Token::Comma && nested_paren_level == 1 {
-                    found_vector = true;
-                }
-                lookahead_n += 1;
-            }
-            // we are dealing with a vector
-            if found_vector {
-                let mut v: Vec<ExpressionType> = Vec::new();
-                // consume open paren to solve recursive loop from not moving
-                self.expect_next(Token::OpenParen)?;
-                loop {
-                    v.push(self.parse_expression_r()?);
-                    match self.next_token()? {
-                        Token::Comma => {
-                            continue;
-                        }
-                        Token::CloseParen => {
-                            return Ok(ExpressionType::Vector(v));
+            self.next_token()?;
+            let first = self.parse_expression_r()?;
+            let t = self.next_token()?;
+            match t.kind {
+                TokenKind::CloseParen => Ok(first),
+                TokenKind::Comma => {
+                    let mut v: Vec<ExpressionType> = vec![first];
+                    loop {
+                        v.push(self.parse_expression_r()?);
+                        let t = self.next_token()?;
+                        match t.kind {
+                            TokenKind::Comma => {
+                                continue;
+                            }
+                            TokenKind::CloseParen => {
+                                return Ok(ExpressionType::new(
+                                    ExpressionKind::Vector(v),
+                                    self.span_from(start),
+                                ));
+                            }
+                            _ => return Err(ParseError::BadVecElement(t.span).into()),
                         }
-                        _ => return Err(ParseError::BadVecElement.into()),
                     }
                 }
-            } else {
-                self.next_token()?;
-                let r = self.parse_expression_r();
-                self.expect_next(Token::CloseParen)?;
-                r
+                _ => Err(ParseError::Expected(TokenKind::CloseParen, t).into()),
             }
         } else {
             // could be value or fn call; hard to discern what a floating id means
             // right now string is handled above for performance? that might change
             // not sure
-            match t {
+            match t.kind {
                 // value
-                Token::Number(_n) => self.parse_number(),
+                TokenKind::Number(_n) => self.parse_number(),
                 // might be value? could also be fn call here
-                Token::Id(id) => {
+                TokenKind::Id(id) => {
                     //lookahead next run parse r as function call -- we have not consumed, so look 2
                     let next_t = self.lookahead(2)?;
-                    if next_t == Token::OpenParen {
-                        // consume singular so we know we have ( on deck
-                        self.next_token()?;
-                        // check for function call with no arguments
-                        if self.lookahead(2)? == Token::CloseParen {
-                            self.next_token()?; // (
-                            self.next_token()?; // )
-                            return Ok(ExpressionType::Expr(ArithmeticExpression::Unary(
-                                UnaryOperator::FunctionCall(id),
-                                Box::new(ExpressionType::None),
-                            )));
-                        }
-                        let expr = self.parse_expression_r()?;
-                        // hopefully a vector? should be or we crash probs
-                        Ok(ExpressionType::Expr(ArithmeticExpression::Unary(
-                            UnaryOperator::FunctionCall(id),
-                            Box::new(expr),
-                        )))
+                    if next_t.kind == TokenKind::OpenParen {
+                        self.next_token()?; // id
+                        let arguments = self.parse_arguments()?;
+                        Ok(ExpressionType::new(
+                            ExpressionKind::Expr(ArithmeticExpression::Call(id, arguments)),
+                            self.span_from(start),
+                        ))
                     } else {
                         self.next_token()?;
-                        Ok(ExpressionType::Variable(id))
+                        Ok(ExpressionType::new(ExpressionKind::Variable(id), t.span))
                     }
                 }
-                _ => {
-                    return Err(ParseError::NeedsClearerError(
-                        "Expected value within operator parsing, got token",
-                    )
-                    .into());
-                }
+                _ => Err(ParseError::ExpectedDescribed("a value", t).into()),
             }
         }
     }
 
-    fn parse_for(&mut self) -> Result<ForData> {
+    // (a, b, ..) of a call, up to and including the )
+    fn parse_arguments(&mut self) -> Result<Vec<ExpressionType>> {
+        self.expect_next(TokenKind::OpenParen)?;
+        let mut arguments = Vec::new();
+        if self.lookahead(1)?.kind == TokenKind::CloseParen {
+            self.next_token()?;
+            return Ok(arguments);
+        }
+        loop {
+            arguments.push(self.parse_expression_r()?);
+            let t = self.next_token()?;
+            match t.kind {
+                TokenKind::Comma => continue,
+                TokenKind::CloseParen => return Ok(arguments),
+                _ => return Err(ParseError::Expected(TokenKind::CloseParen, t).into()),
+            }
+        }
+    }
+
+    fn parse_for(&mut self, start: Span) -> Result<ForData> {
         let mut for_data = ForData {
             initial_definitions: HashMap::new(),
             condition: Condition::None,
             body: Block::new(),
+            span: start,
         };
 
-        self.expect_next(Token::OpenParen)?;
+        self.expect_next(TokenKind::OpenParen)?;
         let mut t = self.next_token()?;
-        while t != Token::CloseParen {
-            match t {
-                Token::Id(id) => {
-                    self.expect_next(Token::Assign)?;
+        while t.kind != TokenKind::CloseParen {
+            match t.kind {
+                TokenKind::Id(id) => {
+                    self.expect_next(TokenKind::Assign)?;
                     let range = self.parse_range()?;
                     for_data.initial_definitions.insert(id, range);
                 }
-                Token::Comma => {}
-                _ => return Err(ParseError::InvalidForDef.into()),
+                TokenKind::Comma => {}
+                _ => return Err(ParseError::InvalidForDef(t.span).into()),
             }
             t = self.next_token()?;
         }
 
-        if self.lookahead(1)? != Token::OpenBlock {
+        if self.lookahead(1)?.kind != TokenKind::OpenBlock {
             let t = self.next_token()?;
-            self.expect_next(Token::OpenParen)?;
+            self.expect_next(TokenKind::OpenParen)?;
             let expression = self.parse_expression()?;
-            match t {
-                Token::Condition(ConditionToken::When) => {
+            match t.kind {
+                TokenKind::Condition(ConditionToken::When) => {
                     for_data.condition = Condition::When(expression);
                 }
-                Token::Condition(ConditionToken::Unless) => {
+                TokenKind::Condition(ConditionToken::Unless) => {
                     for_data.condition = Condition::Unless(expression);
                 }
                 _ => return Err(ParseError::Token(t).into()),
             }
-            self.expect_next(Token::CloseParen)?;
+            self.expect_next(TokenKind::CloseParen)?;
         }
 
         for_data.body = self.parse_block()?;
+        for_data.span = self.span_from(start);
 
         Ok(for_data)
     }
 
     fn parse_number(&mut self) -> Result<ExpressionType> {
         let t = self.next_token()?;
-        match t {
-            Token::Number(n) => {
-                if n.contains(".") {
-                    Ok(ExpressionType::Float(n.parse::<f64>()?))
+        match t.kind {
+            TokenKind::Number(n) => {
+                let kind = if n.contains(".") {
+                    n.parse::<f64>().map(ExpressionKind::Float).ok()
                 } else {
-                    Ok(ExpressionType::Int(n.parse::<i64>()?))
+                    n.parse::<i64>().map(ExpressionKind::Int).ok()
+                };
+                match kind {
+                    Some(kind) => Ok(ExpressionType::new(kind, t.span)),
+                    None => Err(ParseError::InvalidNumber(t.span).into()),
                 }
             }
-            _ => Err(ParseError::InvalidNumber.into()),
+            _ => Err(ParseError::InvalidNumber(t.span).into()),
         }
     }
 
     fn parse_range(&mut self) -> Result<ExpressionType> {
         let start = self.parse_number()?;
-        self.expect_next(Token::RangeSeparator)?;
+        self.expect_next(TokenKind::RangeSeparator)?;
         let end = self.parse_number()?;
+        let span = start.span.to(end.span);
 
         // both must be ints
-        let error = Err(ParseError::RangeMustBeInt.into());
-        match start {
-            ExpressionType::Int(s) => match end {
-                ExpressionType::Int(e) => Ok(ExpressionType::Range(s, e)),
+        let error = Err(ParseError::RangeMustBeInt(span).into());
+        match start.kind {
+            ExpressionKind::Int(s) => match end.kind {
+                ExpressionKind::Int(e) => {
+                    Ok(ExpressionType::new(ExpressionKind::Range(s, e), span))
+                }
                 _ => error,
             },
             _ => error,
@@ -566,45 +881,176 @@ impl Parser {
 
     fn parse_wait(&mut self) -> Result<WaitData> {
         let expr = self.parse_expression()?;
-        match expr {
-            ExpressionType::Duration(wd) => Ok(*wd),
-            _err => Err(ParseError::NeedsClearerError("Found invalid time expression").into()),
+        match expr.kind {
+            ExpressionKind::Duration(wd) => Ok(*wd),
+            _err => Err(
+                ParseError::NeedsClearerError("Found invalid time expression", expr.span).into(),
+            ),
         }
     }
 
-    fn parse_spawn(&mut self) -> Result<SpawnData> {
-        let block = self.parse_block()?;
+    fn parse_pattern_call(&mut self, start: Span) -> Result<PatternCallData> {
+        let name = self.next_token()?;
+        let pattern = match name.kind {
+            TokenKind::Id(pattern) => pattern,
+            _ => return Err(ParseError::ExpectedDescribed("a pattern name", name).into()),
+        };
+        let arguments = if self.lookahead(1)?.kind == TokenKind::OpenParen {
+            self.parse_arguments()?
+        } else {
+            Vec::new()
+        };
+        self.expect_next(TokenKind::Semicolon)?;
+        Ok(PatternCallData {
+            pattern,
+            arguments,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_spawn(&mut self, start: Span) -> Result<SpawnData> {
+        let definitions = self.parse_values()?;
         Ok(SpawnData {
-            definitions: block.definitions,
+            definitions,
+            span: self.span_from(start),
         })
     }
 
-    fn parse_path(&mut self) -> Result<NamedToplevel> {
+    fn parse_path(&mut self, start: Span) -> Result<NamedToplevel> {
         let name = self.next_token().context("Parsing path...")?;
-        if let Token::Id(name) = name {
-            let arguments = self.parse_expression()?;
-            self.expect_next(Token::Assign)?;
+        if let TokenKind::Id(name) = name.kind {
+            let arguments = self.parse_parameters()?;
+            self.expect_next(TokenKind::Assign)?;
 
             let definitions = self.parse_values()?;
             let path_node = Node::Path(PathData {
                 definitions,
                 arguments,
+                span: self.span_from(start),
             });
             Ok((name, path_node))
         } else {
-            Err(ParseError::Expected(Token::String("Id".to_string()), name).into())
+            Err(ParseError::ExpectedDescribed("an identifier", name).into())
         }
     }
 
-    fn parse_bullet(&mut self) -> Result<NamedToplevel> {
+    fn parse_function(&mut self, start: Span) -> Result<NamedToplevel> {
+        let name = self.next_token().context("Parsing function...")?;
+        if let TokenKind::Id(name) = name.kind {
+            let arguments = self.parse_parameters()?;
+            self.expect_next(TokenKind::Assign)?;
+            let body = self.parse_expression()?;
+            let function_node = Node::Function(FunctionData {
+                arguments,
+                body,
+                span: self.span_from(start),
+            });
+            Ok((name, function_node))
+        } else {
+            Err(ParseError::ExpectedDescribed("an identifier", name).into())
+        }
+    }
+
+    // (a, b, ..) as a vector of variables, () and (a) included
+    fn parse_parameters(&mut self) -> Result<ExpressionType> {
+        let open = self.expect_next(TokenKind::OpenParen)?;
+        let mut parameters = Vec::new();
+        if self.lookahead(1)?.kind == TokenKind::CloseParen {
+            self.next_token()?;
+        } else {
+            loop {
+                let t = self.next_token()?;
+                match t.kind {
+                    TokenKind::Id(id) => {
+                        parameters.push(ExpressionType::new(ExpressionKind::Variable(id), t.span))
+                    }
+                    _ => return Err(ParseError::ExpectedDescribed("a parameter name", t).into()),
+                }
+                let t = self.next_token()?;
+                match t.kind {
+                    TokenKind::Comma => continue,
+                    TokenKind::CloseParen => break,
+                    _ => return Err(ParseError::Expected(TokenKind::CloseParen, t).into()),
+                }
+            }
+        }
+        Ok(ExpressionType::new(
+            ExpressionKind::Vector(parameters),
+            self.span_from(open.span),
+        ))
+    }
+
+    fn parse_bullet(&mut self, start: Span) -> Result<NamedToplevel> {
         let name = self.next_token().context("Parsing bullet...")?;
-        if let Token::Id(name) = name {
-            self.expect_next(Token::Assign)?;
+        if let TokenKind::Id(name) = name.kind {
+            let parent = if self.lookahead(1)?.kind == TokenKind::Colon {
+                self.next_token()?;
+                let parent = self.next_token()?;
+                match parent.kind {
+                    TokenKind::Id(parent) => Some(parent),
+                    _ => return Err(ParseError::ExpectedDescribed("a bullet name", parent).into()),
+                }
+            } else {
+                None
+            };
+            self.expect_next(TokenKind::Assign)?;
             let definitions = self.parse_values()?;
-            let bullet_node = Node::Bullet(BulletData { definitions });
+            let bullet_node = Node::Bullet(BulletData {
+                parent,
+                definitions,
+                span: self.span_from(start),
+            });
             Ok((name, bullet_node))
         } else {
-            Err(ParseError::Expected(Token::String("Id".to_string()), name).into())
+            Err(ParseError::ExpectedDescribed("an identifier", name).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // everything around the broken statements is kept, and each is reported where it is
+    #[test]
+    fn recovers_from_several_errors() {
+        let source = include_str!("../../../examples/errors.pattern").to_string();
+        let (head, errors) = Parser::new(Lexer::new(source)).parse_recovering();
+
+        let spans: Vec<(usize, usize)> = errors
+            .iter()
+            .map(|e| {
+                e.downcast_ref::<ParseError>()
+                    .expect("a parse error")
+                    .span()
+            })
+            .map(|span| (span.line, span.column))
+            .collect();
+        assert_eq!(spans, [(3, 28), (12, 44), (14, 12)]);
+
+        let mut names: Vec<&str> = head.definitions.keys().map(|k| k.as_str()).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["après", "broken", "mid_sized", "ok"]);
+        match &head.definitions["mid_sized"] {
+            Node::Bullet(bd) => {
+                assert!(bd.definitions.contains_key("sprite"));
+                assert!(!bd.definitions.contains_key("speed"));
+            }
+            other => panic!("mid_sized is a bullet, not {:?}", other),
+        }
+        let statements = match &head.definitions["broken"] {
+            Node::Pattern(pd) => match &pd.definitions["actions"].kind {
+                ExpressionKind::Block(block) => &block.statements,
+                other => panic!("actions should be a block, not {:?}", other),
+            },
+            other => panic!("broken is a pattern, not {:?}", other),
+        };
+        match statements.as_slice() {
+            [Node::Spawn(sd), Node::Wait(_)] => {
+                assert!(sd.definitions.contains_key("type"));
+                assert!(!sd.definitions.contains_key("rotation"));
+            }
+            other => panic!("expected the spawn and the wait, found {:?}", other),
         }
     }
 }
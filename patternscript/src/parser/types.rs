@@ -18,3 +18,60 @@ pub enum Op {
     GTE,
     LTE,
 }
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Op::Test => "==",
+            Op::And => "and",
+            Op::Or => "or",
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Exp => "^",
+            Op::GT => ">",
+            Op::LT => "<",
+            Op::GTE => ">=",
+            Op::LTE => "<=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+// location of a token or node within the source
+//   start/end are byte offsets (end exclusive), line/column are 1-based and point at start
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    // covering span from the start of self to the end of other
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end.max(self.end),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -1,18 +1,20 @@
+use patternscript::analysis;
+use patternscript::diagnostics::Severity;
 use patternscript::interpreter::entity::{Entity, Hitbox};
 use patternscript::interpreter::evaluate::*;
 use patternscript::interpreter::*;
-use patternscript::parser::lexer::{Lexer, Token};
+use patternscript::parser::lexer::{Lexer, TokenKind};
 use patternscript::parser::parser::*;
 use patternscript::parser::types::Op;
 use std::env;
 use std::fs;
 use std::process;
 
-use cgmath::{Deg, Vector2, Vector3};
+use cgmath::{Deg, Vector2};
 
 const USAGE: &'static str = "./patternscript [action] [file]
     actions:
-        -p : parse
+        -p : parse and check
         -l : lex
         -i : initialize interpreter and dump details from pattern
     file: 
@@ -29,20 +31,33 @@ fn main() {
     if &args[1] == "-l" {
         let contents = fs::read_to_string(&args[2]);
         let mut lexer = Lexer::new(contents.unwrap());
-        let mut token = lexer.next_token();
-        while token != Some(Token::EOF) {
-            println!("{:?}", token.unwrap());
-            token = lexer.next_token();
+        let mut token = lexer.next_token().unwrap();
+        while token.kind != TokenKind::EOF {
+            println!("{} {:?}", token.span, token.kind);
+            token = lexer.next_token().unwrap();
         }
-        println!("{:?}", token.unwrap());
+        println!("{} {:?}", token.span, token.kind);
         process::exit(0);
     }
 
     if &args[1] == "-p" {
-        let head = Parser::parse_from_file(args[2].clone());
-        match head {
-            Ok(h) => println!("{:?}", h),
-            Err(e) => println!("{}", e),
+        let source = fs::read_to_string(&args[2]).unwrap_or_else(|e| {
+            eprintln!("{}: {}", args[2], e);
+            process::exit(1);
+        });
+        let diagnostics = match Parser::parse_with_diagnostics(source.clone()) {
+            Ok(Node::Head(h)) => {
+                println!("{:?}", h);
+                analysis::check(&h)
+            }
+            Ok(_) => Vec::new(),
+            Err(diagnostics) => diagnostics,
+        };
+        for d in &diagnostics {
+            eprintln!("{}", d.render(&args[2], &source));
+        }
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            process::exit(1);
         }
         process::exit(0);
     }
@@ -59,16 +74,15 @@ fn main() {
             rotation: Deg(0.0),
             speed: Some(20.0),
             lifetime: 600,
-            color: Vector3 { x: 255, y: 0, z: 0 },
             hitbox: Hitbox {
                 size: Vector2 { x: 8, y: 8 },
                 offset: Vector2 { x: 0.0, y: 0.0 },
                 hitbox_type: entity::HitboxType::Rectangle,
             },
             behavior: entity::Behavior::Pattern(pattern_name.clone()),
-            position_fn: None,
-            velocity_fn: None,
-            instance_vars: None,
+            collides: false,
+            bounds: entity::BoundsPolicy::Unbounded,
+            ..Entity::new()
         };
 
         world.spawn_direct(&e);
@@ -81,22 +95,42 @@ fn main() {
     }
 
     if &args[1] == "-e" {
-        let expr = ExpressionType::Expr(ArithmeticExpression::Binary(
+        let expr: ExpressionType = ExpressionKind::Expr(ArithmeticExpression::Binary(
             Op::Add,
-            Box::new(ExpressionType::Int(1)),
-            Box::new(ExpressionType::Expr(ArithmeticExpression::Binary(
-                Op::Sub,
-                Box::new(ExpressionType::Variable("x".to_string())),
-                Box::new(ExpressionType::Expr(ArithmeticExpression::Unary(
-                    UnaryOperator::Negate,
-                    Box::new(ExpressionType::Int(10)),
-                ))),
-            ))),
-        ));
+            Box::new(ExpressionKind::Int(1).into()),
+            Box::new(
+                ExpressionKind::Expr(ArithmeticExpression::Binary(
+                    Op::Sub,
+                    Box::new(ExpressionKind::Variable("x".to_string()).into()),
+                    Box::new(
+                        ExpressionKind::Expr(ArithmeticExpression::Unary(
+                            UnaryOperator::Negate,
+                            Box::new(ExpressionKind::Int(10).into()),
+                        ))
+                        .into(),
+                    ),
+                ))
+                .into(),
+            ),
+        ))
+        .into();
 
         let mut values = Values::new();
-        values.insert("x".to_string(), ExpressionType::Int(10));
+        values.insert("x".to_string(), ExpressionKind::Int(10).into());
 
-        println!("{:?}", expr.eval(&values));
+        // the tree walker and the vm should always agree
+        let reference = expr.clone().eval(&values);
+        println!("tree walker: {:?}", reference);
+        match bytecode::compile(&expr, &values, &[]) {
+            Ok(program) => {
+                let result = vm::execute(&program, &[]);
+                println!("vm: {:?}", result);
+                if !vm::agrees(&result, &reference) {
+                    eprintln!("vm and tree walker disagree");
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => println!("vm: didn't compile: {}", e),
+        }
     }
 }
@@ -1,11 +1,12 @@
-use cgmath::{Deg, Vector2, Vector3};
+use cgmath::{Deg, Vector2, Vector4};
 use ggez::event::{self, EventHandler};
-use ggez::graphics::{self, Color};
+use ggez::graphics::{self, Color, Drawable};
 use ggez::{mint, timer};
 use ggez::{Context, ContextBuilder, GameResult};
 
-use patternscript::interpreter::entity::{Entity, Hitbox};
+use patternscript::interpreter::entity::{BlendMode, Entity, Hitbox, HitboxType};
 use patternscript::interpreter::evaluate::*;
+use patternscript::interpreter::bounds::Playfield;
 use patternscript::interpreter::*;
 use patternscript::parser::lexer::{Lexer, Token};
 use patternscript::parser::parser::*;
@@ -28,42 +29,60 @@ fn main() {
         rotation: Deg(90.0),
         speed: Some(50.0),
         lifetime: 600000,
-        color: Vector3 {
+        color: Vector4 {
             x: 255,
             y: 0,
             z: 255,
+            w: 255,
         },
         hitbox: Hitbox {
             size: Vector2 { x: 8, y: 8 },
             offset: Vector2 { x: 0.0, y: 0.0 },
-            hitbox_type: entity::HitboxType::Rectangle,
+            hitbox_type: HitboxType::Rectangle,
         },
         behavior: entity::Behavior::Pattern(args[2].clone()),
-        position_fn: None,
-        velocity_fn: None,
-        instance_vars: None,
+        collides: false,
+        bounds: entity::BoundsPolicy::Unbounded,
+        ..Entity::new()
     };
     world.spawn_direct(&e);
+    // bullets touching the mouse disappear
+    world.set_hurtbox(
+        0,
+        Some(Hitbox {
+            size: Vector2 { x: 12, y: 12 },
+            offset: Vector2 { x: 0.0, y: 0.0 },
+            hitbox_type: entity::HitboxType::Circle,
+        }),
+    );
+    world.collision.despawn_on_hit = true;
+    // bullets leaving the window are dropped instead of living out their lifetime
+    let (width, height) = graphics::drawable_size(&ctx);
+    world.playfield = Some(Playfield::from_size(width as f64, height as f64));
 
     let my_game = MyGame::new(&mut ctx, world);
     event::run(ctx, event_loop, my_game);
 }
 
-struct MyGame<'a> {
-    world: Interpreter<'a>,
+struct MyGame {
+    world: Interpreter,
 }
 
-impl<'a> MyGame<'a> {
-    pub fn new(_ctx: &mut Context, world: Interpreter<'a>) -> MyGame<'a> {
+impl MyGame {
+    pub fn new(_ctx: &mut Context, world: Interpreter) -> MyGame {
         // Load/create resources such as images here.
         MyGame { world }
     }
 }
 
-impl<'a> EventHandler for MyGame<'a> {
+impl EventHandler for MyGame {
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
         const DESIRED_FPS: u32 = 120;
         while timer::check_update_time(ctx, DESIRED_FPS) {
+            // the mouse stands in for the player
+            let mouse = ggez::input::mouse::position(ctx);
+            self.world
+                .set_target(0, Vector2::new(mouse.x as f64, mouse.y as f64), None);
             self.world.step();
         }
         Ok(())
@@ -72,28 +91,13 @@ impl<'a> EventHandler for MyGame<'a> {
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx, Color::WHITE);
 
-        for e in &self.world.entities {
-            let color = e.entity.color;
-
-            let color = graphics::Color {
-                r: color[0] as f32 / 255.0,
-                g: color[1] as f32 / 255.0,
-                b: color[2] as f32 / 255.0,
-                a: 1.0,
-            };
-            let rx = e.entity.position.x as f32 - (e.entity.hitbox.size.x / 2) as f32;
-            let ry = e.entity.position.y as f32 - (e.entity.hitbox.size.y / 2) as f32;
-
-            let rect = graphics::Mesh::new_rectangle(
-                ctx,
-                graphics::DrawMode::fill(),
-                graphics::Rect::new(rx, ry, 8.0, 8.0),
-                color,
-            )?;
-
-            if (rx < 1200. && rx > 0. && ry > 0. && ry < 800.) {
-                graphics::draw(ctx, &rect, graphics::DrawParam::default())?;
-            }
+        // lowest z_order first so higher ones end up on top, in spawn order otherwise
+        let mut entities: Vec<&Entity> = self.world.entities.iter().map(|e| &e.entity).collect();
+        entities.sort_by_key(|e| e.z_order);
+        for e in entities {
+            let mut mesh = entity_mesh(ctx, e)?;
+            mesh.set_blend_mode(Some(blend_mode(e.blend)));
+            graphics::draw(ctx, &mesh, graphics::DrawParam::default())?;
         }
 
         let fps = ggez::timer::fps(ctx);
@@ -103,3 +107,40 @@ impl<'a> EventHandler for MyGame<'a> {
         graphics::present(ctx)
     }
 }
+
+// sprites name a host's own assets, here the hitbox's shape stands in for them
+fn entity_mesh(ctx: &mut Context, e: &Entity) -> GameResult<graphics::Mesh> {
+    let color = Color::from_rgba(e.color.x, e.color.y, e.color.z, e.color.w);
+    let scale = e.scale as f32;
+    let width = (e.hitbox.size.x as f32 * scale).max(1.0);
+    let height = (e.hitbox.size.y as f32 * scale).max(1.0);
+    // the offset turns with the entity, as it does for collisions
+    let (sin, cos) = e.rotation.0.to_radians().sin_cos();
+    let (ox, oy) = (e.hitbox.offset.x as f32, e.hitbox.offset.y as f32);
+    let x = e.position.x as f32 + ox * cos - oy * sin;
+    let y = e.position.y as f32 + ox * sin + oy * cos;
+    let fill = graphics::DrawMode::fill();
+    match e.hitbox.hitbox_type {
+        HitboxType::Rectangle => graphics::Mesh::new_rectangle(
+            ctx,
+            fill,
+            graphics::Rect::new(x - width / 2.0, y - height / 2.0, width, height),
+            color,
+        ),
+        HitboxType::Ellipse => {
+            graphics::Mesh::new_ellipse(ctx, fill, [x, y], width / 2.0, height / 2.0, 0.1, color)
+        }
+        HitboxType::Circle => {
+            graphics::Mesh::new_circle(ctx, fill, [x, y], width / 2.0, 0.1, color)
+        }
+    }
+}
+
+fn blend_mode(blend: BlendMode) -> graphics::BlendMode {
+    match blend {
+        BlendMode::Alpha => graphics::BlendMode::Alpha,
+        BlendMode::Add => graphics::BlendMode::Add,
+        BlendMode::Subtract => graphics::BlendMode::Subtract,
+        BlendMode::Multiply => graphics::BlendMode::Multiply,
+    }
+}